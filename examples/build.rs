@@ -0,0 +1,10 @@
+use std::path::PathBuf;
+
+fn main() {
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    sursface_build::embed_dir(
+        "src/cube_camera/assets",
+        out_dir.join("cube_camera_assets.rs"),
+    )
+    .unwrap();
+}
@@ -1,19 +1,27 @@
-use cgmath::{perspective, Deg, Matrix4, Point3, SquareMatrix, Vector3};
 use sursface::app::AppState;
+use sursface::camera::{Camera, OrbitCamera};
 use sursface::display::Display;
+use sursface::math::{Mat4, ToUniform, Transform, Vec3};
+use sursface::std::debug_lines::DebugLines;
 use sursface::std::models::{cube, quad_uvs, VertexPositionNormalUv};
+use sursface::std::source::EmbeddedSource;
+use sursface::std::target::TargetFormat;
 use sursface::std::{
-    clear, create_render_pipeline, create_sampler_entry, create_shader, create_texture,
-    create_texture_layout_entry_from_image, create_uniforms, get_framebuffer,
+    clear, create_render_pipeline, create_sampler_entry, create_texture, create_uniforms,
+    get_framebuffer, load_shader, load_texture_layout_entry,
 };
+use sursface::time::now_secs;
 use sursface::wgpu::util::{BufferInitDescriptor, DeviceExt};
 use sursface::wgpu::{
     BindGroup, BindGroupEntry, BindingResource, Buffer, BufferAddress, BufferUsages, Color,
     CommandEncoderDescriptor, PipelineLayoutDescriptor, RenderPipeline, VertexAttribute,
     VertexBufferLayout, VertexFormat, VertexStepMode,
 };
-use sursface::winit::dpi::PhysicalPosition;
-use sursface::winit::event::{Touch, WindowEvent};
+use sursface::winit::keyboard::KeyCode;
+use sursface::winit::event_loop::EventLoopProxy;
+
+static ASSETS: EmbeddedSource =
+    EmbeddedSource::new(include!(concat!(env!("OUT_DIR"), "/cube_camera_assets.rs")));
 
 fn main() {
     #[cfg(not(target_arch = "wasm32"))]
@@ -35,9 +43,10 @@ struct CubeState {
     vertex_buffer: Buffer,
     texture_bind_group: BindGroup,
     uniforms: Uniforms,
-    yaw: f64,
-    pitch: f64,
-    pan_speed: f64,
+    camera: OrbitCamera,
+    last_timestep: f32,
+    gizmos: DebugLines,
+    show_gizmos: bool,
 }
 
 #[repr(C)]
@@ -48,17 +57,15 @@ pub struct Uniforms {
 }
 
 impl AppState for CubeState {
-    fn new(display: &mut Display) -> Self {
+    type UserEvent = ();
+
+    fn new(display: &mut Display, _proxy: Option<EventLoopProxy<()>>) -> Self {
         let device = &display.device;
 
-        let shader = create_shader(device, include_str!("assets/shader.wgsl"));
+        let shader = load_shader(device, &ASSETS, "shader.wgsl").unwrap();
 
-        let (texture_bind_group_entry, texture_view) = create_texture_layout_entry_from_image(
-            device,
-            &display.queue,
-            include_bytes!("assets/dice.png"),
-            0,
-        );
+        let (texture_bind_group_entry, texture_view) =
+            load_texture_layout_entry(device, &display.queue, &ASSETS, "dice.png", 0).unwrap();
         let (sampler_entry, sampler) = create_sampler_entry(device, 1);
 
         let (texture_bind_group_layout, texture_bind_group) = create_texture(
@@ -79,8 +86,8 @@ impl AppState for CubeState {
         let (uniform_buffer, uniform_bind_group_layout, uniform_bind_group) = create_uniforms(
             device,
             Uniforms {
-                model_view_proj: Matrix4::identity().into(),
-                camera_pan: Matrix4::identity().into(),
+                model_view_proj: Mat4::IDENTITY.to_uniform(),
+                camera_pan: Mat4::IDENTITY.to_uniform(),
             },
             0,
         );
@@ -126,7 +133,7 @@ impl AppState for CubeState {
                 quad_uvs((0.25, 1f32 / 3f32), (0.25 + 0.25, 2f32 / 3f32)), // 4
                 quad_uvs((0.75, 1f32 / 3f32), (0.25 + 0.75, 2f32 / 3f32)), // 3
                 quad_uvs((0.50, 0f32 / 3f32), (0.25 + 0.50, 1f32 / 3f32)), // 2
-                quad_uvs((0.50, 2f32 / 3f32), (0.25 + 0.50, 3f32 / 3f32)), // 5
+                quad_uvs((0.50, 2f32 / 3f32), (0.25 + 0.50, 1f32)), // 5
             ])),
             usage: BufferUsages::VERTEX,
         });
@@ -138,21 +145,46 @@ impl AppState for CubeState {
             vertex_buffer,
             texture_bind_group,
             uniforms: Uniforms {
-                model_view_proj: Matrix4::identity().into(),
-                camera_pan: Matrix4::identity().into(),
+                model_view_proj: Mat4::IDENTITY.to_uniform(),
+                camera_pan: Mat4::IDENTITY.to_uniform(),
             },
-            yaw: 0f64,
-            pitch: 0f64,
-            pan_speed: 0.4f64,
+            camera: OrbitCamera::looking_at(Vec3::new(3.0, 3.0, 3.0), Vec3::ZERO),
+            last_timestep: now_secs(),
+            gizmos: DebugLines::new(&display.device, &TargetFormat::from(&*display), false),
+            show_gizmos: true,
         }
     }
 
     fn draw(&mut self, display: &mut Display) {
+        let dt = now_secs() - self.last_timestep;
+        self.last_timestep = now_secs();
+
+        self.camera.update(display, dt);
+
+        if display.input.key_just_pressed(KeyCode::KeyG) {
+            self.show_gizmos = !self.show_gizmos;
+        }
+        if self.show_gizmos {
+            let gray = Color {
+                r: 0.4,
+                g: 0.4,
+                b: 0.4,
+                a: 0.5,
+            };
+            self.gizmos.grid([0.0, -1.0, 0.0], 8.0, 8, gray);
+            self.gizmos.aabb([-1.0; 3], [1.0; 3], Color::BLACK);
+            self.gizmos.axes([0.0; 3], 2.0);
+        }
+        self.gizmos
+            .set_view_projection(self.camera.view_projection().to_uniform());
+        self.gizmos.prepare(&display.device, &display.queue);
+        self.gizmos.clear();
+
         let clear_color = Color {
             r: 252.0 / 255.0,
             g: 241.0 / 255.0,
             b: 139.0 / 255.0,
-            a: 255.0 / 255.0,
+            a: 1.0,
         };
 
         let output = {
@@ -160,23 +192,14 @@ impl AppState for CubeState {
                 .device
                 .create_command_encoder(&CommandEncoderDescriptor { label: None });
 
-            let (output, view) = get_framebuffer(&display.surface);
+            let (output, view) = get_framebuffer(display.surface());
             {
                 let mut rpass = clear(&view, &mut encoder, clear_color);
 
-                let aspect_ratio = display.config.width as f32 / display.config.height as f32;
+                let model = Transform::IDENTITY;
+                let mvp = self.camera.view_projection() * model.matrix();
 
-                let model = Matrix4::identity();
-
-                let view = Matrix4::look_at_rh(
-                    Point3::new(3.0, 3.0, 3.0),
-                    Point3::new(0.0, 0.0, 0.0),
-                    Vector3::unit_y(),
-                );
-                let proj = perspective(Deg(45.0), aspect_ratio, 0.1, 10.0);
-                let mvp = proj * view * model;
-
-                self.uniforms.model_view_proj = mvp.into();
+                self.uniforms.model_view_proj = mvp.to_uniform();
 
                 let queue = &display.queue;
                 queue.write_buffer(
@@ -192,6 +215,8 @@ impl AppState for CubeState {
                     rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
                     rpass.draw(0..36, 0..1);
                 }
+
+                self.gizmos.render(&mut rpass);
             }
 
             {
@@ -203,25 +228,4 @@ impl AppState for CubeState {
 
         output.present();
     }
-
-    fn event<'a>(&mut self, _display: &mut Display, event: WindowEvent) {
-        let movement = {
-            match event {
-                WindowEvent::Touch(Touch { location, .. }) => Some(location),
-                WindowEvent::CursorMoved { position, .. } => Some(position),
-                _ => None,
-            }
-        };
-
-        if let Some(PhysicalPosition { x, y }) = movement {
-            self.yaw = x;
-            self.pitch = -y;
-
-            self.uniforms.camera_pan = (Matrix4::from_angle_y(Deg(self.yaw * self.pan_speed))
-                * Matrix4::from_angle_x(Deg(self.pitch * self.pan_speed)))
-            .cast()
-            .unwrap()
-            .into();
-        }
-    }
 }
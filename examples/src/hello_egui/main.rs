@@ -0,0 +1,75 @@
+use sursface::app::AppState;
+use sursface::display::Display;
+use sursface::egui;
+use sursface::overlay::egui::EguiOverlay;
+use sursface::overlay::Overlay;
+use sursface::std::target::{clear_target, get_frame};
+use sursface::wgpu::{self, Color};
+use sursface::winit::event_loop::EventLoopProxy;
+
+fn main() {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        sursface::start::create_window_desktop::<EguiState>(1280, 720);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn start_browser(canvas: sursface::wgpu::web_sys::HtmlCanvasElement) {
+    sursface::start::create_window_browser::<EguiState>(canvas);
+}
+
+struct EguiState {
+    color: [f32; 3],
+    clicks: u32,
+}
+
+impl AppState for EguiState {
+    type UserEvent = ();
+
+    fn new(_display: &mut Display, _proxy: Option<EventLoopProxy<()>>) -> Self {
+        Self {
+            color: [100.0 / 255.0, 149.0 / 255.0, 237.0 / 255.0],
+            clicks: 0,
+        }
+    }
+
+    fn create_overlay(display: &Display) -> Option<Box<dyn Overlay>> {
+        Some(Box::new(EguiOverlay::new(display)))
+    }
+
+    fn draw(&mut self, display: &mut Display) {
+        if let Some(overlay) = display.overlay_mut::<EguiOverlay>() {
+            egui::Window::new("Hello egui").show(overlay.context(), |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Background");
+                    ui.color_edit_button_rgb(&mut self.color);
+                });
+                if ui.button("Click me").clicked() {
+                    self.clicks += 1;
+                }
+                ui.label(format!("Clicked {} times", self.clicks));
+            });
+        }
+
+        let [r, g, b] = self.color;
+        let clear_color = Color {
+            r: r as f64,
+            g: g as f64,
+            b: b as f64,
+            a: 1.0,
+        };
+
+        let frame = get_frame(display);
+        let mut encoder = display
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        clear_target(&frame.target, &mut encoder, clear_color);
+        display.draw_overlay(&mut encoder, &frame.target);
+
+        display.queue.submit(std::iter::once(encoder.finish()));
+        frame.present();
+    }
+}
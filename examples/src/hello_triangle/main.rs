@@ -2,6 +2,7 @@ use sursface::app::AppState;
 use sursface::display::Display;
 use sursface::std::{clear, create_render_pipeline, create_shader, get_framebuffer};
 use sursface::wgpu::{self, Color, RenderPipeline};
+use sursface::winit::event_loop::EventLoopProxy;
 
 fn main() {
     #[cfg(not(target_arch = "wasm32"))]
@@ -21,7 +22,9 @@ struct TriangleState {
 }
 
 impl AppState for TriangleState {
-    fn new(display: &mut Display) -> Self {
+    type UserEvent = ();
+
+    fn new(display: &mut Display, _proxy: Option<EventLoopProxy<()>>) -> Self {
         let device = &display.device;
 
         let shader = create_shader(device, include_str!("assets/shader.wgsl"));
@@ -41,7 +44,7 @@ impl AppState for TriangleState {
             r: 100.0 / 255.0,
             g: 149.0 / 255.0,
             b: 237.0 / 255.0,
-            a: 255.0 / 255.0,
+            a: 1.0,
         };
 
         let device = &display.device;
@@ -49,7 +52,7 @@ impl AppState for TriangleState {
         let mut encoder =
             device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 
-        let (output, view) = get_framebuffer(&display.surface);
+        let (output, view) = get_framebuffer(display.surface());
         {
             let mut rpass = clear(&view, &mut encoder, clear_color);
 
@@ -1,6 +1,7 @@
-use sursface::app::AppState;
+use sursface::app::{AppState, RedrawMode};
 use sursface::display::Display;
 use sursface::wgpu::{self, TextureView};
+use sursface::winit::event_loop::EventLoopProxy;
 
 fn main() {
     #[cfg(not(target_arch = "wasm32"))]
@@ -19,12 +20,16 @@ pub fn start_browser(canvas: sursface::wgpu::web_sys::HtmlCanvasElement) {
 struct EmptyState {}
 
 impl AppState for EmptyState {
-    fn new<'a>(_display: &mut Display) -> Self {
+    type UserEvent = ();
+
+    fn new<'a>(display: &mut Display, _proxy: Option<EventLoopProxy<()>>) -> Self {
+        // Nothing here changes on its own, so only draw when the window asks for it.
+        display.set_redraw_mode(RedrawMode::OnDemand);
         Self {}
     }
 
     fn draw<'a>(&mut self, display: &mut Display) {
-        let output = display.surface.get_current_texture().unwrap();
+        let output = display.surface().get_current_texture().unwrap();
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
@@ -44,7 +49,7 @@ impl AppState for EmptyState {
     }
 }
 
-fn clear<'a>(display: &mut Display, view: &TextureView, color: sursface::wgpu::Color) {
+fn clear(display: &mut Display, view: &TextureView, color: sursface::wgpu::Color) {
     let mut encoder = display
         .device
         .create_command_encoder(&wgpu::CommandEncoderDescriptor {
@@ -55,7 +60,7 @@ fn clear<'a>(display: &mut Display, view: &TextureView, color: sursface::wgpu::C
         let _render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: None,
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &view,
+                view,
                 resolve_target: None,
                 ops: wgpu::Operations {
                     load: wgpu::LoadOp::Clear(color),
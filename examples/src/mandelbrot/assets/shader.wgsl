@@ -8,22 +8,10 @@ struct Uniforms {
 
 @group(0) @binding(0) var<uniform> uniforms: Uniforms;
 
-struct VertexOutput {
-    @builtin(position) position: vec4<f32>,
-    @location(0) fragUV: vec2<f32>,
-};
-
-@vertex
-fn vs_main(@location(0) position: vec3<f32>, @location(1) uv: vec2<f32>) -> VertexOutput {
-    var output: VertexOutput;
-    output.position = vec4<f32>(position, 1.0);
-    output.fragUV = uv;
-    return output;
-}
-
 @fragment
-fn fs_main(@location(0) fragUV: vec2<f32>) -> @location(0) vec4<f32> {
+fn fs_main(input: FullscreenOutput) -> @location(0) vec4<f32> {
     let max_iter: u32 = 200u;
+    let fragUV = vec2<f32>(input.uv.x, 1.0 - input.uv.y);
     
     let centered_uv = (fragUV * 2.0 - vec2<f32>(1.0, 1.0)) * vec2<f32>(uniforms.aspect_ratio, 1.0);
     let c = uniforms.translation + centered_uv * uniforms.scale;
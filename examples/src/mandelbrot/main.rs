@@ -1,22 +1,19 @@
 use bytemuck::{Pod, Zeroable};
-use cgmath::{Vector2, Zero};
-use std::fmt::Display as FmtDisplay;
 use sursface::app::AppState;
+use sursface::camera::{Camera, PanZoomCamera};
 use sursface::display::Display;
-use sursface::std::models::{quad_no_normal, quad_uvs, VertexPositionUv};
-use sursface::std::{
-    clear, create_render_pipeline, create_shader, create_uniforms, get_framebuffer,
-};
+use sursface::input::gesture::{Gesture, GestureRecognizer};
+use sursface::log;
+use sursface::math::{ToUniform, Vec2};
+use sursface::std::draw2d::Draw2D;
+use sursface::std::post::FullscreenPass;
+use sursface::std::target::TargetFormat;
+use sursface::std::{clear, create_uniforms, get_framebuffer};
 use sursface::time::now_secs;
-use sursface::wgpu::util::DeviceExt;
-use sursface::wgpu::{
-    BindGroup, Buffer, BufferAddress, BufferUsages, Color, CommandEncoderDescriptor,
-    PipelineLayoutDescriptor, RenderPipeline, VertexAttribute, VertexBufferLayout, VertexFormat,
-    VertexStepMode,
-};
+use sursface::wgpu::{BindGroup, Buffer, Color, CommandEncoderDescriptor};
 use sursface::winit::dpi::PhysicalPosition;
-use sursface::winit::event::{ElementState, MouseButton, Touch, TouchPhase, WindowEvent};
-use sursface::{log, wgpu};
+use sursface::winit::event::WindowEvent;
+use sursface::winit::event_loop::EventLoopProxy;
 
 fn main() {
     #[cfg(not(target_arch = "wasm32"))]
@@ -31,47 +28,26 @@ pub fn start_browser(canvas: sursface::wgpu::web_sys::HtmlCanvasElement) {
     sursface::start::create_window_browser::<MandelbrotState>(canvas);
 }
 
-#[derive(Clone)]
-enum InteractionState {
-    /// Not pressing or holding down.
-    Idle { pre_tapped_at: Option<f32> },
-    /// Holding down but not dragging yet.
-    PanningIdle { pressed_down_at: f32 },
-    /// Holding down and dragging.
-    Panning,
-    /// Holding down without dragging for a bit then possibly dragging.
-    ZoomingIn,
-    /// Holding down without dragging for a bit (with pre-tap) then possibly dragging.
-    ZoomingOut,
-}
-
-impl FmtDisplay for InteractionState {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            InteractionState::Idle { pre_tapped_at } => {
-                write!(f, "Idle {{ pre_tapped_at: {:?} }}", pre_tapped_at)
-            }
-            InteractionState::PanningIdle { pressed_down_at } => {
-                write!(f, "PanningIdle {{ pressed_down_at: {} }}", pressed_down_at)
-            }
-            InteractionState::Panning => write!(f, "Panning"),
-            InteractionState::ZoomingIn => write!(f, "Zooming"),
-            InteractionState::ZoomingOut => write!(f, "ZoomingOut"),
-        }
-    }
+#[derive(Clone, Copy, PartialEq)]
+enum Zoom {
+    None,
+    /// Holding down without dragging for a bit, then possibly dragging.
+    In,
+    /// Holding down right after a tap, then possibly dragging.
+    Out,
 }
 
 struct MandelbrotState {
-    render_pipeline: RenderPipeline,
-    vertex_buffer: Buffer,
+    fullscreen_pass: FullscreenPass,
     uniform_buffer: Buffer,
     uniform_bind_group: BindGroup,
     uniforms: Uniforms,
     scale_speed: f32,
-    last_cursor_location: PhysicalPosition<f32>,
-    cursor_location: PhysicalPosition<f32>,
     last_timestep: f32,
-    interaction_state: InteractionState,
+    gestures: GestureRecognizer,
+    zoom: Zoom,
+    camera: PanZoomCamera,
+    hud: Draw2D,
 }
 
 #[repr(C)]
@@ -85,16 +61,19 @@ pub struct Uniforms {
 }
 
 impl AppState for MandelbrotState {
-    fn new(display: &mut Display) -> Self {
+    type UserEvent = ();
+
+    fn new(display: &mut Display, _proxy: Option<EventLoopProxy<()>>) -> Self {
+        display.set_target_fps(Some(60.0));
+
         let device = &display.device;
         let aspect_ratio = display.config.width as f32 / display.config.height as f32;
 
-        let shader = create_shader(device, include_str!("assets/shader.wgsl"));
         let (uniform_buffer, uniform_bind_group_layout, uniform_bind_group) = create_uniforms(
             device,
             Uniforms {
-                translation: Vector2::zero().into(),
-                cursor_pos: Vector2::zero().into(),
+                translation: Vec2::ZERO.to_uniform(),
+                cursor_pos: Vec2::ZERO.to_uniform(),
                 scale: 4.0,
                 aspect_ratio,
                 _padding: [0.0; 2],
@@ -102,67 +81,31 @@ impl AppState for MandelbrotState {
             0,
         );
 
-        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &[&uniform_bind_group_layout],
-            push_constant_ranges: &[],
-        });
-
-        let render_pipeline = create_render_pipeline(
-            display,
-            pipeline_layout,
-            shader,
-            &[VertexBufferLayout {
-                array_stride: std::mem::size_of::<VertexPositionUv>() as BufferAddress,
-                step_mode: VertexStepMode::Vertex,
-                attributes: &[
-                    VertexAttribute {
-                        offset: 0,
-                        shader_location: 0,
-                        format: VertexFormat::Float32x3,
-                    },
-                    VertexAttribute {
-                        offset: 12,
-                        shader_location: 1,
-                        format: VertexFormat::Float32x2,
-                    },
-                ],
-            }],
+        let target_format = TargetFormat::from(&*display);
+        let fullscreen_pass = FullscreenPass::new(
+            device,
+            &target_format,
+            include_str!("assets/shader.wgsl"),
+            &[&uniform_bind_group_layout],
         );
 
-        let quad_uvs = quad_uvs((0.0, 0.0), (1.0, 1.0));
-
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: None,
-            contents: bytemuck::cast_slice(&quad_no_normal(
-                [-1.0, -1.0, 1.0],
-                [1.0, -1.0, 1.0],
-                [1.0, 1.0, 1.0],
-                [-1.0, 1.0, 1.0],
-                quad_uvs,
-            )),
-            usage: BufferUsages::VERTEX,
-        });
-
         Self {
-            render_pipeline,
-            vertex_buffer,
+            fullscreen_pass,
             uniform_buffer,
             uniform_bind_group,
             uniforms: Uniforms {
-                translation: Vector2::zero().into(),
-                cursor_pos: Vector2::zero().into(),
+                translation: Vec2::ZERO.to_uniform(),
+                cursor_pos: Vec2::ZERO.to_uniform(),
                 scale: 4.0,
                 aspect_ratio,
                 _padding: [0.0; 2],
             },
             scale_speed: 0.5f32,
-            last_cursor_location: PhysicalPosition::new(0.0, 0.0),
-            cursor_location: PhysicalPosition::new(0.0, 0.0),
             last_timestep: now_secs(),
-            interaction_state: InteractionState::Idle {
-                pre_tapped_at: None,
-            },
+            gestures: GestureRecognizer::default(),
+            zoom: Zoom::None,
+            camera: PanZoomCamera::new(Vec2::ZERO, 8.0),
+            hud: Draw2D::new(device, &target_format),
         }
     }
 
@@ -181,44 +124,96 @@ impl AppState for MandelbrotState {
             r: 100.0 / 255.0,
             g: 149.0 / 255.0,
             b: 237.0 / 255.0,
-            a: 255.0 / 255.0,
+            a: 1.0,
         };
 
-        self.interaction_state = match self.interaction_state.clone() {
-            state @ InteractionState::PanningIdle { pressed_down_at } => {
-                if now_secs() - pressed_down_at > 1f32 {
+        self.gestures.update();
+        for gesture in self.gestures.drain() {
+            match gesture {
+                Gesture::Press { tap_count: 2.., .. } => {
+                    log::info!("Started zooming out at {}", now_secs());
+                    self.zoom = Zoom::Out;
+                }
+                Gesture::LongPress { .. } if self.zoom == Zoom::None => {
                     log::info!("Started zooming in at {}", now_secs());
-                    InteractionState::ZoomingIn
-                } else {
-                    state
+                    self.zoom = Zoom::In;
+                }
+                Gesture::Release { .. } => {
+                    self.zoom = Zoom::None;
                 }
+                _ => (),
             }
-            state => state,
-        };
+        }
 
-        match self.interaction_state {
-            InteractionState::ZoomingIn => {
-                self.uniforms.scale *= self.scale_speed.powf(dt);
+        match self.zoom {
+            Zoom::In => {
+                self.camera.height *= self.scale_speed.powf(dt);
             }
-            InteractionState::ZoomingOut => {
-                self.uniforms.scale /= self.scale_speed.powf(dt);
+            Zoom::Out => {
+                self.camera.height /= self.scale_speed.powf(dt);
+            }
+            Zoom::None => (),
+        }
+
+        // dragging pans, pinching and scrolling zoom
+        self.camera.update(display, dt);
+        self.uniforms.translation = self.camera.center.to_uniform();
+        // the shader spans `scale` units from the center to the top edge
+        self.uniforms.scale = self.camera.height / 2.0;
+
+        let cursor_location = display
+            .input
+            .touches()
+            .next()
+            .map(|touch| touch.position)
+            .or(display.input.cursor_position())
+            .unwrap_or(PhysicalPosition::new(0.0, 0.0));
+
+        if self.zoom != Zoom::None {
+            let center = (cursor_location.x as f32, cursor_location.y as f32);
+            let ring = Color {
+                r: 1.0,
+                g: 1.0,
+                b: 1.0,
+                a: 0.6,
+            };
+            self.hud.circle_outline(center, 24.0, 2.0, ring);
+            self.hud.line(
+                (center.0 - 8.0, center.1),
+                (center.0 + 8.0, center.1),
+                2.0,
+                ring,
+            );
+            if self.zoom == Zoom::In {
+                self.hud.line(
+                    (center.0, center.1 - 8.0),
+                    (center.0, center.1 + 8.0),
+                    2.0,
+                    ring,
+                );
             }
-            _ => (),
         }
+        self.hud.prepare(
+            &display.device,
+            &display.queue,
+            display.config.width,
+            display.config.height,
+        );
+        self.hud.clear();
 
         let output = {
             let mut encoder = display
                 .device
                 .create_command_encoder(&CommandEncoderDescriptor { label: None });
 
-            let (output, view) = get_framebuffer(&display.surface);
+            let (output, view) = get_framebuffer(display.surface());
 
             {
                 let mut rpass = clear(&view, &mut encoder, clear_color);
 
                 self.uniforms.cursor_pos = [
-                    self.cursor_location.x / display.config.width as f32,
-                    self.cursor_location.y / display.config.height as f32,
+                    cursor_location.x as f32 / display.config.width as f32,
+                    cursor_location.y as f32 / display.config.height as f32,
                 ];
 
                 let queue = &display.queue;
@@ -228,12 +223,9 @@ impl AppState for MandelbrotState {
                     bytemuck::cast_slice(&[self.uniforms]),
                 );
 
-                {
-                    rpass.set_pipeline(&self.render_pipeline);
-                    rpass.set_bind_group(0, &self.uniform_bind_group, &[]);
-                    rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-                    rpass.draw(0..6, 0..1);
-                }
+                self.fullscreen_pass
+                    .draw(&mut rpass, &[&self.uniform_bind_group]);
+                self.hud.render(&mut rpass);
             }
 
             display.queue.submit(std::iter::once(encoder.finish()));
@@ -244,122 +236,7 @@ impl AppState for MandelbrotState {
         output.present();
     }
 
-    fn event<'a>(&mut self, display: &mut Display, event: WindowEvent) {
-        match event {
-            WindowEvent::CursorMoved { position, .. } => self.handle_cursor_move(display, position),
-            WindowEvent::MouseInput {
-                state: ElementState::Pressed,
-                button: MouseButton::Left,
-                ..
-            } => self.interaction_state = self.handle_cursor_down(),
-            WindowEvent::MouseInput {
-                state: ElementState::Released,
-                button: MouseButton::Left,
-                ..
-            } => self.interaction_state = self.handle_cursor_up(),
-            WindowEvent::Touch(Touch {
-                phase: TouchPhase::Moved,
-                location,
-                ..
-            }) => self.handle_cursor_move(display, location),
-            WindowEvent::Touch(Touch {
-                phase: TouchPhase::Started,
-                location,
-                ..
-            }) => {
-                self.cursor_location = PhysicalPosition {
-                    x: location.x as f32,
-                    y: location.y as f32,
-                };
-
-                self.interaction_state = self.handle_cursor_down();
-            }
-            WindowEvent::Touch(Touch {
-                phase: TouchPhase::Ended,
-                ..
-            }) => self.interaction_state = self.handle_cursor_up(),
-            _ => {}
-        }
-    }
-}
-
-impl MandelbrotState {
-    fn handle_cursor_move(&mut self, display: &mut Display, position: PhysicalPosition<f64>) {
-        self.cursor_location = PhysicalPosition {
-            x: position.x as f32,
-            y: position.y as f32,
-        };
-
-        if self.cursor_location == self.last_cursor_location {
-            return;
-        }
-
-        match self.interaction_state.clone() {
-            InteractionState::PanningIdle { pressed_down_at: _ }
-            | InteractionState::ZoomingIn
-            | InteractionState::ZoomingOut
-            | InteractionState::Panning => {
-                let dx = (self.cursor_location.x - self.last_cursor_location.x)
-                    / display.size.width as f32;
-                let dy = (self.cursor_location.y - self.last_cursor_location.y)
-                    / display.size.height as f32;
-
-                self.uniforms.translation[0] -= dx * self.uniforms.scale;
-                self.uniforms.translation[1] += dy * self.uniforms.scale;
-
-                const WIGGLE_TOLERANCE: f32 = 0.001f32;
-
-                if !(dx < WIGGLE_TOLERANCE && dy < WIGGLE_TOLERANCE) {
-                    self.interaction_state = InteractionState::Panning
-                }
-            }
-            _ => (),
-        };
-
-        self.last_cursor_location = self.cursor_location;
-    }
-
-    fn handle_cursor_down(&mut self) -> InteractionState {
-        self.last_cursor_location = self.cursor_location;
-
-        match self.interaction_state.clone() {
-            InteractionState::Idle {
-                pre_tapped_at: Some(pre_tapped_at),
-            } => {
-                if now_secs() - pre_tapped_at < 1f32 {
-                    log::info!("Started zooming out at {}", now_secs());
-                    InteractionState::ZoomingOut
-                } else {
-                    InteractionState::PanningIdle {
-                        pressed_down_at: now_secs(),
-                    }
-                }
-            }
-            InteractionState::Idle { .. } => InteractionState::PanningIdle {
-                pressed_down_at: now_secs(),
-            },
-            state => state,
-        }
-    }
-
-    fn handle_cursor_up(&mut self) -> InteractionState {
-        match self.interaction_state.clone() {
-            InteractionState::PanningIdle { pressed_down_at } => {
-                let elapsed = now_secs() - pressed_down_at;
-                let pre_tapped_at = if elapsed < 0.3f32 {
-                    Some(pressed_down_at)
-                } else {
-                    None
-                };
-
-                InteractionState::Idle { pre_tapped_at }
-            }
-            InteractionState::ZoomingIn
-            | InteractionState::ZoomingOut
-            | InteractionState::Panning => InteractionState::Idle {
-                pre_tapped_at: None,
-            },
-            state => state,
-        }
+    fn event(&mut self, _display: &mut Display, event: WindowEvent) {
+        self.gestures.handle_event(&event);
     }
 }
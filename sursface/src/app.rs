@@ -1,38 +1,227 @@
-use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use web_time::{Duration, Instant};
 use winit::application::ApplicationHandler;
 use winit::dpi::PhysicalSize;
-use winit::event::{DeviceEvent, WindowEvent};
-use winit::event_loop::ActiveEventLoop;
-use winit::window::{Window, WindowId};
+use winit::event::{DeviceEvent, StartCause, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoopProxy};
+use winit::window::{Window, WindowAttributes, WindowId};
 
 #[cfg(target_arch = "wasm32")]
 extern crate console_error_panic_hook;
 
-use super::display::Display;
+use super::display::{AppRequest, Display};
+use super::overlay::{self, Overlay};
 
-pub(crate) struct App<'a, State: AppState> {
-    pub display: Option<Arc<Mutex<Display<'a>>>>,
+/// The winit [`ApplicationHandler`] that drives an [`AppState`]. The functions in
+/// [`crate::start`] run one for you; use it directly to drive your own event loop.
+///
+/// Displays and state are owned directly and only touched on the event loop thread. A panic in
+/// an [`AppState`] callback is logged once and exits the loop.
+pub struct App<'a, State: AppState> {
+    pub displays: HashMap<WindowId, Display<'a>>,
+    /// The first window. Closing it exits the app.
+    pub main_window: Option<WindowId>,
+    /// The window with keyboard focus, which receives device events.
+    pub focused_window: Option<WindowId>,
+    /// Set between [`ApplicationHandler::suspended`] and the next resume; nothing is drawn.
+    pub suspended: bool,
+    /// The control flow asked for through [`Display::set_control_flow`]. Only used in
+    /// [`RedrawMode::Continuous`]; in [`RedrawMode::OnDemand`] the app waits for events and
+    /// redraw deadlines.
+    pub control_flow: ControlFlow,
+    pub redraw_mode: RedrawMode,
+    /// Frame rate limit for [`RedrawMode::Continuous`], set through [`Display::set_target_fps`].
+    pub target_fps: Option<f32>,
+    /// When the frame limiter draws next.
+    next_frame: Option<Instant>,
     #[cfg(not(target_arch = "wasm32"))]
     pub initial_size: PhysicalSize<u32>,
     #[cfg(target_arch = "wasm32")]
     pub canvas: wgpu::web_sys::HtmlCanvasElement,
-    pub state: Option<Arc<Mutex<State>>>,
+    pub state: Option<State>,
+    /// Handed to [`AppState::new`], see [`App::with_proxy`].
+    pub proxy: Option<EventLoopProxy<State::UserEvent>>,
+    /// Set once an [`AppState`] callback panicked; the loop is shutting down and no more
+    /// callbacks run.
+    pub panicked: bool,
+}
+
+/// The frame rates [`Display::set_target_fps`] accepts.
+pub const TARGET_FPS_RANGE: std::ops::RangeInclusive<f32> = 0.1..=1000.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RedrawMode {
+    /// Draws a new frame as soon as the last one is done, or when the control flow's deadline
+    /// passes.
+    #[default]
+    Continuous,
+    /// Draws only after input, a resize, [`Display::mark_dirty`], or a deadline set with
+    /// [`Display::redraw_at`].
+    OnDemand,
 }
 
 pub trait AppState {
-    fn new(display: &mut Display) -> Self;
+    /// Events sent from other threads through the proxy passed to [`AppState::new`]; use `()`
+    /// if unused.
+    type UserEvent: Send + 'static;
+
+    /// Creates the state once the main window exists. `proxy` wakes the event loop and sends
+    /// events to [`AppState::user_event`] from other threads; it is `None` when the [`App`] was
+    /// created without [`App::with_proxy`].
+    fn new(display: &mut Display, proxy: Option<EventLoopProxy<Self::UserEvent>>) -> Self;
     fn create_display(window: Window) -> Display<'static> {
         Display::from_window(window)
     }
 
+    /// Creates the UI overlay that gets window events first and draws on top of the app.
+    fn create_overlay(display: &Display) -> Option<Box<dyn Overlay>> {
+        let _ = display;
+        None
+    }
+
     fn draw(&mut self, display: &mut Display);
 
+    /// Called when the user tries to close a window. Return `false` to keep it open, e.g. to
+    /// ask about unsaved changes first.
+    fn close_requested(&mut self, display: &mut Display) -> bool {
+        let _ = display; // suppress warning
+        true
+    }
+
     fn event(&mut self, display: &mut Display, event: WindowEvent) {
         let (_, _) = (event, display); // suppress warning
     }
+    /// Receives the events the overlay captured instead of [`AppState::event`].
+    fn captured_event(&mut self, display: &mut Display, event: WindowEvent) {
+        let (_, _) = (event, display); // suppress warning
+    }
     fn device_event(&mut self, display: &mut Display, event: DeviceEvent) {
         let (_, _) = (event, display); // suppress warning
     }
+    /// Receives events sent through the proxy passed to [`AppState::new`], with the main display.
+    fn user_event(&mut self, display: &mut Display, event: Self::UserEvent) {
+        let (_, _) = (event, display); // suppress warning
+    }
+
+    /// Called with the display of a window opened through [`Display::open_window`].
+    fn window_opened(&mut self, display: &mut Display) {
+        let _ = display; // suppress warning
+    }
+    /// Called after a window other than the main one has been closed.
+    fn window_closed(&mut self, id: WindowId) {
+        let _ = id; // suppress warning
+    }
+
+    /// Called when the platform suspends the app. The displays' surfaces are dropped until
+    /// [`AppState::on_resume`].
+    fn on_suspend(&mut self) {}
+    /// Called when the app resumes after [`AppState::on_suspend`], once the surfaces have been
+    /// recreated. The state is kept; only `new` runs on the first resume.
+    fn on_resume(&mut self, display: &mut Display) {
+        let _ = display; // suppress warning
+    }
+    /// Called once when the event loop is shutting down.
+    fn on_exit(&mut self) {}
+    fn on_focus_changed(&mut self, display: &mut Display, focused: bool) {
+        let (_, _) = (display, focused); // suppress warning
+    }
+    /// Called when the window becomes hidden or visible again. Redraws of hidden windows are
+    /// paused.
+    fn on_occluded(&mut self, display: &mut Display, occluded: bool) {
+        let (_, _) = (display, occluded); // suppress warning
+    }
+}
+
+/// Events that can change what the app shows, which trigger a redraw in [`RedrawMode::OnDemand`].
+fn is_input(event: &WindowEvent) -> bool {
+    matches!(
+        event,
+        WindowEvent::KeyboardInput { .. }
+            | WindowEvent::ModifiersChanged(_)
+            | WindowEvent::Ime(_)
+            | WindowEvent::CursorMoved { .. }
+            | WindowEvent::CursorEntered { .. }
+            | WindowEvent::CursorLeft { .. }
+            | WindowEvent::MouseWheel { .. }
+            | WindowEvent::MouseInput { .. }
+            | WindowEvent::Touch(_)
+            | WindowEvent::PinchGesture { .. }
+            | WindowEvent::PanGesture { .. }
+            | WindowEvent::RotationGesture { .. }
+            | WindowEvent::DoubleTapGesture { .. }
+            | WindowEvent::DroppedFile(_)
+            | WindowEvent::HoveredFile(_)
+            | WindowEvent::HoveredFileCancelled
+            | WindowEvent::Focused(_)
+            | WindowEvent::ScaleFactorChanged { .. }
+            | WindowEvent::ThemeChanged(_)
+    )
+}
+
+/// Runs an [`AppState`] callback, logging a panic instead of unwinding into winit.
+fn catch<R>(callback: &str, f: impl FnOnce() -> R) -> Option<R> {
+    match std::panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => Some(result),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown panic");
+            log::error!(
+                "AppState panicked in {}: {}; shutting down",
+                callback,
+                message
+            );
+            None
+        }
+    }
+}
+
+/// How the loop paces frames, copied out of [`App`] so the scheduling can be worked out without
+/// an event loop.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Pacing {
+    mode: RedrawMode,
+    control_flow: ControlFlow,
+    /// The frame limiter's period, if one is set.
+    period: Option<Duration>,
+    next_frame: Option<Instant>,
+}
+
+impl Pacing {
+    /// Handles waking up at `requested`, a deadline the loop asked for. Returns the pacing to
+    /// go on with when every display should redraw, which is only in [`RedrawMode::Continuous`].
+    fn resumed(mut self, requested: Instant, now: Instant) -> Option<Self> {
+        if self.mode != RedrawMode::Continuous {
+            return None;
+        }
+
+        if let Some(period) = self.period {
+            // Schedule from the requested time rather than now, so wake-up latency doesn't
+            // add up. After falling more than a frame behind, skip ahead instead of catching up.
+            let next = requested + period;
+            self.next_frame = Some(if next <= now { now + period } else { next });
+        } else if let ControlFlow::WaitUntil(_) = self.control_flow {
+            // Once passed, the deadline would wake the loop on every iteration.
+            self.control_flow = ControlFlow::Wait;
+        }
+        Some(self)
+    }
+
+    /// The control flow to sleep with. `redraw_deadline` is the earliest pending
+    /// [`Display::redraw_at`] deadline, which only counts in [`RedrawMode::OnDemand`].
+    fn wait(&self, redraw_deadline: Option<Instant>) -> ControlFlow {
+        match (self.mode, self.period.and(self.next_frame)) {
+            (RedrawMode::Continuous, Some(next_frame)) => ControlFlow::WaitUntil(next_frame),
+            (RedrawMode::Continuous, None) => self.control_flow,
+            (RedrawMode::OnDemand, _) => {
+                redraw_deadline.map_or(ControlFlow::Wait, ControlFlow::WaitUntil)
+            }
+        }
+    }
 }
 
 fn init_logger() {
@@ -84,7 +273,8 @@ fn init_logger() {
         std::panic::set_hook(Box::new(console_error_panic_hook::hook));
     }
 
-    dispatch.apply().unwrap();
+    // Another logger may already be installed, e.g. by an embedding app or a second `App`.
+    let _ = dispatch.apply();
 }
 
 impl<'a, State: AppState> App<'a, State> {
@@ -93,8 +283,17 @@ impl<'a, State: AppState> App<'a, State> {
         log::debug!("Setting window size");
         App {
             initial_size: winit::dpi::PhysicalSize::new(width, height),
-            display: None,
+            displays: HashMap::new(),
+            main_window: None,
+            focused_window: None,
+            suspended: false,
+            control_flow: ControlFlow::Poll,
+            redraw_mode: RedrawMode::Continuous,
+            target_fps: None,
+            next_frame: None,
             state: None,
+            proxy: None,
+            panicked: false,
         }
     }
 
@@ -103,64 +302,505 @@ impl<'a, State: AppState> App<'a, State> {
         log::debug!("Setting canvas size");
         App {
             canvas,
-            display: None,
+            displays: HashMap::new(),
+            main_window: None,
+            focused_window: None,
+            suspended: false,
+            control_flow: ControlFlow::Poll,
+            redraw_mode: RedrawMode::Continuous,
+            target_fps: None,
+            next_frame: None,
             state: None,
+            proxy: None,
+            panicked: false,
+        }
+    }
+
+    /// Hands `proxy` to [`AppState::new`].
+    pub fn with_proxy(mut self, proxy: EventLoopProxy<State::UserEvent>) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    pub fn display(&self, id: WindowId) -> Option<&Display<'a>> {
+        self.displays.get(&id)
+    }
+
+    pub fn display_mut(&mut self, id: WindowId) -> Option<&mut Display<'a>> {
+        self.displays.get_mut(&id)
+    }
+
+    pub fn main_display(&self) -> Option<&Display<'a>> {
+        self.displays.get(&self.main_window?)
+    }
+
+    fn add_display(&mut self, mut display: Display<'a>) -> WindowId {
+        display.overlay = State::create_overlay(&display);
+        let id = display.id();
+        self.displays.insert(id, display);
+        id
+    }
+
+    fn shut_down(&mut self, event_loop: &ActiveEventLoop) {
+        self.panicked = true;
+        event_loop.exit();
+    }
+
+    /// Carries out what the state asked for, until no requests are left.
+    fn handle_requests(&mut self, event_loop: &ActiveEventLoop) {
+        loop {
+            let requests: Vec<AppRequest> = self
+                .displays
+                .values_mut()
+                .flat_map(|display| std::mem::take(&mut display.requests))
+                .collect();
+            if requests.is_empty() || self.panicked {
+                return;
+            }
+
+            for request in requests {
+                match request {
+                    AppRequest::Open(attributes) => self.open_window(event_loop, *attributes),
+                    AppRequest::Close(id) => self.close_window(event_loop, id),
+                    AppRequest::ControlFlow(control_flow) => {
+                        self.control_flow = control_flow;
+                        if self.redraw_mode == RedrawMode::Continuous {
+                            event_loop.set_control_flow(control_flow);
+                        }
+                    }
+                    AppRequest::RedrawMode(mode) => self.set_redraw_mode(event_loop, mode),
+                    AppRequest::TargetFps(fps) => self.set_target_fps(event_loop, fps),
+                    AppRequest::Exit => event_loop.exit(),
+                }
+            }
+        }
+    }
+
+    fn open_window(&mut self, event_loop: &ActiveEventLoop, attributes: WindowAttributes) {
+        let window = match event_loop.create_window(attributes) {
+            Ok(window) => window,
+            Err(e) => {
+                log::error!("Couldn't create window: {}", e);
+                return;
+            }
+        };
+
+        let display = self.main_display().unwrap().share(window);
+        let id = self.add_display(display);
+
+        let (Some(display), Some(state)) = (self.displays.get_mut(&id), self.state.as_mut()) else {
+            return;
+        };
+        if catch("window_opened", || state.window_opened(display)).is_none() {
+            self.shut_down(event_loop);
+        }
+    }
+
+    fn set_redraw_mode(&mut self, event_loop: &ActiveEventLoop, mode: RedrawMode) {
+        self.redraw_mode = mode;
+        if mode == RedrawMode::Continuous {
+            event_loop.set_control_flow(self.control_flow);
+        }
+        for display in self.displays.values() {
+            display.request_redraw();
+        }
+    }
+
+    fn set_target_fps(&mut self, event_loop: &ActiveEventLoop, fps: Option<f32>) {
+        self.target_fps = fps;
+        self.next_frame = fps.map(|_| Instant::now());
+        if fps.is_none() && self.redraw_mode == RedrawMode::Continuous {
+            event_loop.set_control_flow(self.control_flow);
+            for display in self.displays.values() {
+                display.request_redraw();
+            }
+        }
+    }
+
+    fn frame_period(&self) -> Option<Duration> {
+        self.target_fps
+            .filter(|fps| TARGET_FPS_RANGE.contains(fps))
+            .map(|fps| Duration::from_secs_f64(1.0 / fps as f64))
+    }
+
+    fn pacing(&self) -> Pacing {
+        Pacing {
+            mode: self.redraw_mode,
+            control_flow: self.control_flow,
+            period: self.frame_period(),
+            next_frame: self.next_frame,
+        }
+    }
+
+    fn close_window(&mut self, event_loop: &ActiveEventLoop, id: WindowId) {
+        if Some(id) == self.main_window {
+            event_loop.exit();
+            return;
+        }
+
+        if self.displays.remove(&id).is_some() {
+            if self.focused_window == Some(id) {
+                self.focused_window = None;
+            }
+            let Some(state) = self.state.as_mut() else {
+                return;
+            };
+            if catch("window_closed", || state.window_closed(id)).is_none() {
+                self.shut_down(event_loop);
+            }
         }
     }
 }
 
-impl<'a, State: AppState> ApplicationHandler for App<'a, State> {
+impl<'a, State: AppState> ApplicationHandler<State::UserEvent> for App<'a, State> {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.panicked {
+            return;
+        }
+
+        if let Some(state) = self.state.as_mut() {
+            self.suspended = false;
+            for display in self.displays.values_mut() {
+                if display.surface.is_none() {
+                    display.recreate_surface();
+                }
+                display.window.request_redraw();
+            }
+
+            if let Some(display) = self.main_window.and_then(|id| self.displays.get_mut(&id)) {
+                if catch("on_resume", || state.on_resume(display)).is_none() {
+                    return self.shut_down(event_loop);
+                }
+            }
+            self.handle_requests(event_loop);
+            return;
+        }
+
         init_logger();
+        event_loop.set_control_flow(self.control_flow);
 
         #[cfg(not(target_arch = "wasm32"))]
-        {
-            self.display = Some(Arc::new(Mutex::new(State::create_display(
-                Display::create_window_from_size(event_loop, self.initial_size),
-            ))));
-        }
+        let display = State::create_display(Display::create_window_from_size(
+            event_loop,
+            self.initial_size,
+        ));
         #[cfg(target_arch = "wasm32")]
-        {
-            self.display = Some(Arc::new(Mutex::new(Display::from_window(
-                Display::create_window_from_canvas(event_loop, self.canvas.clone()),
-            ))));
+        let display = Display::from_window(Display::create_window_from_canvas(
+            event_loop,
+            self.canvas.clone(),
+        ));
+
+        self.displays.clear();
+        let id = self.add_display(display);
+        self.main_window = Some(id);
+
+        let display = self.displays.get_mut(&id).unwrap();
+        let proxy = self.proxy.clone();
+        match catch("new", || State::new(display, proxy)) {
+            Some(state) => self.state = Some(state),
+            None => return self.shut_down(event_loop),
         }
 
-        let new_state = State::new(&mut self.display.clone().unwrap().lock().unwrap());
-        self.state = Some(Arc::new(Mutex::new(new_state)));
+        self.handle_requests(event_loop);
     }
 
-    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
-        let mut display = self.display.as_ref().clone().unwrap().lock().unwrap();
-        let mut state = self.state.as_ref().clone().unwrap().lock().unwrap();
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
+        if self.panicked {
+            return;
+        }
+        let frame_limited = self.frame_period().is_some();
+        let (Some(display), Some(state)) = (self.displays.get_mut(&id), self.state.as_mut()) else {
+            return;
+        };
 
-        state.event(&mut display, event.clone());
+        let window = Arc::clone(&display.window);
+        let captured = match display.overlay.as_mut() {
+            Some(overlay) => overlay.window_event(&window, &event),
+            None => false,
+        };
 
-        match event {
-            WindowEvent::CloseRequested => {
-                event_loop.exit();
-            }
-            WindowEvent::Resized(physical_size) => {
-                log::debug!("Window resized: {:?}", physical_size);
-                display.resize(physical_size);
-            }
-            WindowEvent::RedrawRequested => {
-                state.draw(&mut display);
-                display.window.as_ref().request_redraw();
+        if !captured || overlay::reaches_input(&event) {
+            display.input.handle_window_event(&event);
+        }
+
+        if self.redraw_mode == RedrawMode::OnDemand && is_input(&event) {
+            window.request_redraw();
+        }
+
+        let handled = catch("window_event", || {
+            if captured {
+                state.captured_event(display, event.clone());
+            } else {
+                state.event(display, event.clone());
             }
-            _ => (),
+
+            match event {
+                WindowEvent::CloseRequested if state.close_requested(display) => {
+                    display.close_window(id);
+                }
+                WindowEvent::Focused(focused) => {
+                    if focused {
+                        self.focused_window = Some(id);
+                    } else if self.focused_window == Some(id) {
+                        self.focused_window = None;
+                    }
+                    state.on_focus_changed(display, focused);
+                }
+                WindowEvent::Occluded(occluded) => {
+                    display.occluded = occluded;
+                    state.on_occluded(display, occluded);
+                    if display.is_visible() {
+                        window.request_redraw();
+                    }
+                }
+                WindowEvent::Resized(physical_size) => {
+                    log::debug!("Window resized: {:?}", physical_size);
+                    display.resize(physical_size);
+                    if display.is_visible() {
+                        window.request_redraw();
+                    }
+                }
+                WindowEvent::RedrawRequested if self.suspended || !display.is_visible() => {}
+                WindowEvent::RedrawRequested => {
+                    if let Some(overlay) = display.overlay.as_mut() {
+                        overlay.begin_frame(&window);
+                    }
+                    display.timing.begin_frame(Instant::now(), self.target_fps);
+                    state.draw(display);
+                    if let Some(overlay) = display.overlay.as_mut() {
+                        overlay.end_frame(&window);
+                    }
+                    display.input.end_frame();
+                    if self.redraw_mode == RedrawMode::Continuous
+                        && self.control_flow == ControlFlow::Poll
+                        && !frame_limited
+                    {
+                        window.request_redraw();
+                    }
+                }
+                _ => (),
+            };
+        });
+
+        if handled.is_none() {
+            return self.shut_down(event_loop);
+        }
+        self.handle_requests(event_loop);
+    }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: State::UserEvent) {
+        if self.panicked {
+            return;
+        }
+        let (Some(display), Some(state)) = (
+            self.main_window.and_then(|id| self.displays.get_mut(&id)),
+            self.state.as_mut(),
+        ) else {
+            return;
         };
+
+        if catch("user_event", || state.user_event(display, event)).is_none() {
+            return self.shut_down(event_loop);
+        }
+        self.handle_requests(event_loop);
     }
 
     fn device_event(
         &mut self,
-        _event_loop: &ActiveEventLoop,
+        event_loop: &ActiveEventLoop,
         _device_id: winit::event::DeviceId,
         event: winit::event::DeviceEvent,
     ) {
-        let mut display = self.display.as_ref().clone().unwrap().lock().unwrap();
-        let mut state = self.state.as_ref().clone().unwrap().lock().unwrap();
+        if self.panicked {
+            return;
+        }
+        let id = self.focused_window.or(self.main_window);
+        let (Some(display), Some(state)) = (
+            id.and_then(|id| self.displays.get_mut(&id)),
+            self.state.as_mut(),
+        ) else {
+            return;
+        };
 
-        state.device_event(&mut display, event.clone());
+        display.input.handle_device_event(&event);
+        if self.redraw_mode == RedrawMode::OnDemand && self.focused_window.is_some() {
+            display.request_redraw();
+        }
+
+        if catch("device_event", || {
+            state.device_event(display, event.clone())
+        })
+        .is_none()
+        {
+            return self.shut_down(event_loop);
+        }
+        self.handle_requests(event_loop);
+    }
+
+    fn new_events(&mut self, event_loop: &ActiveEventLoop, cause: StartCause) {
+        let StartCause::ResumeTimeReached {
+            requested_resume, ..
+        } = cause
+        else {
+            return;
+        };
+        let Some(pacing) = self.pacing().resumed(requested_resume, Instant::now()) else {
+            return;
+        };
+        self.next_frame = pacing.next_frame;
+        if pacing.control_flow != self.control_flow {
+            self.control_flow = pacing.control_flow;
+            event_loop.set_control_flow(self.control_flow);
+        }
+
+        for display in self.displays.values() {
+            display.request_redraw();
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if self.panicked {
+            return;
+        }
+        let now = Instant::now();
+        let mut next = None;
+        if self.redraw_mode == RedrawMode::OnDemand {
+            for display in self.displays.values_mut() {
+                match display.redraw_deadline {
+                    Some(deadline) if deadline <= now => {
+                        display.redraw_deadline = None;
+                        display.request_redraw();
+                    }
+                    Some(deadline) => {
+                        next = Some(next.map_or(deadline, |n: Instant| n.min(deadline)))
+                    }
+                    None => (),
+                }
+            }
+        }
+
+        event_loop.set_control_flow(self.pacing().wait(next));
+    }
+
+    fn suspended(&mut self, event_loop: &ActiveEventLoop) {
+        self.suspended = true;
+        // Some platforms, e.g. Android, destroy the native window's surface until resumed.
+        for display in self.displays.values_mut() {
+            display.drop_surface();
+        }
+        let Some(state) = self.state.as_mut().filter(|_| !self.panicked) else {
+            return;
+        };
+        if catch("on_suspend", || state.on_suspend()).is_none() {
+            self.shut_down(event_loop);
+        }
+    }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        let Some(state) = self.state.as_mut().filter(|_| !self.panicked) else {
+            return;
+        };
+        catch("on_exit", || state.on_exit());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD: Duration = Duration::from_millis(10);
+
+    fn limited(mode: RedrawMode, next_frame: Instant) -> Pacing {
+        Pacing {
+            mode,
+            control_flow: ControlFlow::Poll,
+            period: Some(PERIOD),
+            next_frame: Some(next_frame),
+        }
+    }
+
+    #[test]
+    fn frames_are_scheduled_from_the_requested_time() {
+        let start = Instant::now();
+        let late = start + Duration::from_millis(3);
+        let pacing = limited(RedrawMode::Continuous, start)
+            .resumed(start, late)
+            .unwrap();
+        assert_eq!(pacing.next_frame, Some(start + PERIOD));
+        assert_eq!(pacing.wait(None), ControlFlow::WaitUntil(start + PERIOD));
+    }
+
+    #[test]
+    fn a_long_stall_skips_ahead_instead_of_catching_up() {
+        let start = Instant::now();
+        let stalled = start + Duration::from_secs(2);
+        let pacing = limited(RedrawMode::Continuous, start)
+            .resumed(start, stalled)
+            .unwrap();
+        assert_eq!(pacing.next_frame, Some(stalled + PERIOD));
+
+        let pacing = pacing.resumed(stalled + PERIOD, stalled + PERIOD).unwrap();
+        assert_eq!(pacing.next_frame, Some(stalled + 2 * PERIOD));
+    }
+
+    #[test]
+    fn on_demand_ignores_the_frame_limiter() {
+        let start = Instant::now();
+        let pacing = limited(RedrawMode::OnDemand, start);
+        assert_eq!(pacing.resumed(start, start), None);
+        assert_eq!(pacing.wait(None), ControlFlow::Wait);
+
+        let deadline = start + Duration::from_secs(1);
+        assert_eq!(
+            pacing.wait(Some(deadline)),
+            ControlFlow::WaitUntil(deadline)
+        );
+    }
+
+    #[test]
+    fn switching_back_to_continuous_resyncs() {
+        let start = Instant::now();
+        let mut pacing = limited(RedrawMode::OnDemand, start);
+        let later = start + Duration::from_secs(5);
+
+        // The deadline went stale while on demand, so the loop wakes right away and resyncs.
+        pacing.mode = RedrawMode::Continuous;
+        assert_eq!(pacing.wait(None), ControlFlow::WaitUntil(start));
+        let pacing = pacing.resumed(start, later).unwrap();
+        assert_eq!(pacing.wait(None), ControlFlow::WaitUntil(later + PERIOD));
+    }
+
+    #[test]
+    fn continuous_follows_the_requested_control_flow() {
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(50);
+        let pacing = Pacing {
+            mode: RedrawMode::Continuous,
+            control_flow: ControlFlow::WaitUntil(deadline),
+            period: None,
+            next_frame: None,
+        };
+        assert_eq!(pacing.wait(Some(start)), ControlFlow::WaitUntil(deadline));
+
+        // A passed deadline would wake the loop on every iteration.
+        let pacing = pacing.resumed(deadline, deadline).unwrap();
+        assert_eq!(pacing.control_flow, ControlFlow::Wait);
+        assert_eq!(pacing.wait(None), ControlFlow::Wait);
+
+        let poll = Pacing {
+            control_flow: ControlFlow::Poll,
+            ..pacing
+        };
+        assert_eq!(poll.resumed(start, start), Some(poll));
+    }
+
+    #[test]
+    fn an_out_of_range_target_fps_is_not_limited() {
+        let start = Instant::now();
+        let pacing = Pacing {
+            period: None,
+            ..limited(RedrawMode::Continuous, start)
+        };
+        assert_eq!(pacing.wait(None), ControlFlow::Poll);
     }
 }
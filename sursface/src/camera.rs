@@ -0,0 +1,389 @@
+use winit::dpi::PhysicalPosition;
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+
+use crate::display::Display;
+use crate::input::Input;
+use crate::math::{orthographic, perspective, Mat4, Vec2, Vec3};
+
+/// A controller that turns input into view and projection matrices.
+pub trait Camera {
+    /// Applies the input gathered since the last frame. `dt` is in seconds.
+    fn handle_input(&mut self, input: &Input, dt: f32);
+
+    fn resize(&mut self, width: u32, height: u32);
+
+    fn view(&self) -> Mat4;
+
+    fn projection(&self) -> Mat4;
+
+    fn view_projection(&self) -> Mat4 {
+        self.projection() * self.view()
+    }
+
+    /// Picks up the current surface size and input of `display`; call once per frame in `draw`.
+    fn update(&mut self, display: &Display, dt: f32) {
+        self.resize(display.config.width, display.config.height);
+        self.handle_input(&display.input, dt);
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PerspectiveProjection {
+    /// Vertical field of view in radians.
+    pub fovy: f32,
+    pub aspect: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl PerspectiveProjection {
+    pub fn new(fovy: f32, near: f32, far: f32) -> Self {
+        Self {
+            fovy,
+            aspect: 1.0,
+            near,
+            far,
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.aspect = width as f32 / height as f32;
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        perspective(self.fovy, self.aspect, self.near, self.far)
+    }
+}
+
+impl Default for PerspectiveProjection {
+    fn default() -> Self {
+        Self::new(45f32.to_radians(), 0.1, 100.0)
+    }
+}
+
+/// Tracks one- and two-finger drags, or a mouse drag with `button`, across frames.
+#[derive(Clone, Copy, Debug, Default)]
+struct PointerDrag {
+    last_touch: Option<PhysicalPosition<f64>>,
+    last_distance: Option<f64>,
+}
+
+impl PointerDrag {
+    /// Returns the drag delta in pixels and the pinch scale since the last frame.
+    fn update(&mut self, input: &Input, button: MouseButton) -> ((f64, f64), f64) {
+        let touches: Vec<_> = input.touches().map(|touch| touch.position).collect();
+
+        match touches[..] {
+            [position] => {
+                self.last_distance = None;
+                let delta = self.last_touch.map_or((0.0, 0.0), |last| {
+                    (position.x - last.x, position.y - last.y)
+                });
+                self.last_touch = Some(position);
+                (delta, 1.0)
+            }
+            [a, b, ..] => {
+                self.last_touch = None;
+                let distance = ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt();
+                let scale = self
+                    .last_distance
+                    .filter(|last| *last > 0.0)
+                    .map_or(1.0, |last| distance / last);
+                self.last_distance = Some(distance);
+                ((0.0, 0.0), scale)
+            }
+            [] => {
+                *self = Self::default();
+                if input.mouse_pressed(button) {
+                    (input.cursor_delta(), 1.0)
+                } else {
+                    ((0.0, 0.0), 1.0)
+                }
+            }
+        }
+    }
+}
+
+/// Orbits around `target`: drag with the left mouse button or one finger to rotate, drag with
+/// the right mouse button to pan, scroll or pinch to zoom.
+#[derive(Clone, Copy, Debug)]
+pub struct OrbitCamera {
+    pub target: Vec3,
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Radians per pixel dragged.
+    pub rotate_speed: f32,
+    /// Fraction of the distance zoomed per scrolled line.
+    pub zoom_speed: f32,
+    pub projection: PerspectiveProjection,
+    viewport: (u32, u32),
+    rotate_drag: PointerDrag,
+}
+
+impl OrbitCamera {
+    pub fn new(target: Vec3, distance: f32) -> Self {
+        Self {
+            target,
+            distance,
+            yaw: 0.0,
+            pitch: 0.0,
+            min_distance: 0.1,
+            max_distance: 1000.0,
+            rotate_speed: 0.01,
+            zoom_speed: 0.1,
+            projection: PerspectiveProjection::default(),
+            viewport: (1, 1),
+            rotate_drag: PointerDrag::default(),
+        }
+    }
+
+    /// Starts at `eye`, looking at `target`.
+    pub fn looking_at(eye: Vec3, target: Vec3) -> Self {
+        let offset = eye - target;
+        let distance = offset.length();
+
+        Self {
+            yaw: offset.x.atan2(offset.z),
+            pitch: (offset.y / distance).asin(),
+            ..Self::new(target, distance)
+        }
+    }
+
+    pub fn eye(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+
+        self.target + Vec3::new(cos_pitch * sin_yaw, sin_pitch, cos_pitch * cos_yaw) * self.distance
+    }
+
+    fn viewport_height(&self) -> f32 {
+        self.viewport.1.max(1) as f32
+    }
+}
+
+impl Camera for OrbitCamera {
+    fn handle_input(&mut self, input: &Input, _dt: f32) {
+        let (delta, pinch) = self.rotate_drag.update(input, MouseButton::Left);
+        self.yaw -= delta.0 as f32 * self.rotate_speed;
+        self.pitch += delta.1 as f32 * self.rotate_speed;
+
+        let limit = std::f32::consts::FRAC_PI_2 - 0.01;
+        self.pitch = self.pitch.clamp(-limit, limit);
+
+        if input.mouse_pressed(MouseButton::Right) {
+            let (dx, dy) = input.cursor_delta();
+            let forward = (self.target - self.eye()).normalize();
+            let right = forward.cross(Vec3::Y).normalize();
+            let up = right.cross(forward);
+            // one pixel moves the target by one pixel's worth at the target's depth
+            let pixel =
+                2.0 * self.distance * (self.projection.fovy / 2.0).tan() / self.viewport_height();
+
+            self.target += (-right * dx as f32 + up * dy as f32) * pixel;
+        }
+
+        let scroll = input.scroll_delta().1;
+        self.distance *= (1.0 - self.zoom_speed).powf(scroll) / pinch as f32;
+        self.distance = self.distance.clamp(self.min_distance, self.max_distance);
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.projection.resize(width, height);
+        self.viewport = (width, height);
+    }
+
+    fn view(&self) -> Mat4 {
+        Mat4::look_at_rh(self.eye(), self.target, Vec3::Y)
+    }
+
+    fn projection(&self) -> Mat4 {
+        self.projection.matrix()
+    }
+}
+
+/// First-person camera: WASD to move, Space and left Shift to rise and sink, look around by
+/// dragging with the right mouse button or one finger.
+#[derive(Clone, Copy, Debug)]
+pub struct FlyCamera {
+    pub position: Vec3,
+    /// Zero looks down -Z.
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Units per second.
+    pub speed: f32,
+    /// Radians per pixel of mouse motion.
+    pub look_speed: f32,
+    pub projection: PerspectiveProjection,
+    look_drag: PointerDrag,
+}
+
+impl FlyCamera {
+    pub fn new(position: Vec3) -> Self {
+        Self {
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+            speed: 4.0,
+            look_speed: 0.003,
+            projection: PerspectiveProjection::default(),
+            look_drag: PointerDrag::default(),
+        }
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+
+        Vec3::new(-sin_yaw * cos_pitch, sin_pitch, -cos_yaw * cos_pitch)
+    }
+}
+
+impl Camera for FlyCamera {
+    fn handle_input(&mut self, input: &Input, dt: f32) {
+        // updated every frame so the drag resets once the touches end
+        let (touch_delta, _) = self.look_drag.update(input, MouseButton::Right);
+        // raw motion keeps working while the cursor is grabbed
+        let (dx, dy) = if input.touches().next().is_some() {
+            touch_delta
+        } else if input.mouse_pressed(MouseButton::Right) {
+            input.mouse_motion()
+        } else {
+            (0.0, 0.0)
+        };
+        self.yaw -= dx as f32 * self.look_speed;
+        self.pitch -= dy as f32 * self.look_speed;
+
+        let limit = std::f32::consts::FRAC_PI_2 - 0.01;
+        self.pitch = self.pitch.clamp(-limit, limit);
+
+        let axis = |negative: KeyCode, positive: KeyCode| {
+            input.key_pressed(positive) as i32 as f32 - input.key_pressed(negative) as i32 as f32
+        };
+
+        let forward = self.forward();
+        let right = forward.cross(Vec3::Y).normalize();
+        let movement = forward * axis(KeyCode::KeyS, KeyCode::KeyW)
+            + right * axis(KeyCode::KeyA, KeyCode::KeyD)
+            + Vec3::Y * axis(KeyCode::ShiftLeft, KeyCode::Space);
+
+        if movement.length_squared() > 0.0 {
+            self.position += movement.normalize() * self.speed * dt;
+        }
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.projection.resize(width, height);
+    }
+
+    fn view(&self) -> Mat4 {
+        Mat4::look_to_rh(self.position, self.forward(), Vec3::Y)
+    }
+
+    fn projection(&self) -> Mat4 {
+        self.projection.matrix()
+    }
+}
+
+/// Orthographic 2D camera: drag with the left mouse button or one finger to pan, scroll or pinch
+/// to zoom. Scrolling zooms around the cursor. World Y points up.
+#[derive(Clone, Copy, Debug)]
+pub struct PanZoomCamera {
+    pub center: Vec2,
+    /// World units covered by the viewport height.
+    pub height: f32,
+    pub min_height: f32,
+    pub max_height: f32,
+    /// Fraction of the height zoomed per scrolled line.
+    pub zoom_speed: f32,
+    viewport: (u32, u32),
+    pan_drag: PointerDrag,
+}
+
+impl PanZoomCamera {
+    pub fn new(center: Vec2, height: f32) -> Self {
+        Self {
+            center,
+            height,
+            min_height: 1e-6,
+            max_height: 1e6,
+            zoom_speed: 0.1,
+            viewport: (1, 1),
+            pan_drag: PointerDrag::default(),
+        }
+    }
+
+    pub fn width(&self) -> f32 {
+        self.height * self.viewport.0.max(1) as f32 / self.viewport.1.max(1) as f32
+    }
+
+    /// World units per physical pixel.
+    pub fn pixel_size(&self) -> f32 {
+        self.height / self.viewport.1.max(1) as f32
+    }
+
+    pub fn screen_to_world(&self, position: PhysicalPosition<f64>) -> Vec2 {
+        let pixel = self.pixel_size();
+        let from_center = Vec2::new(
+            position.x as f32 - self.viewport.0 as f32 / 2.0,
+            self.viewport.1 as f32 / 2.0 - position.y as f32,
+        );
+
+        self.center + from_center * pixel
+    }
+
+    /// Zooms by `factor` while keeping the world point under `anchor` in place.
+    pub fn zoom_at(&mut self, anchor: PhysicalPosition<f64>, factor: f32) {
+        let before = self.screen_to_world(anchor);
+        self.height = (self.height * factor).clamp(self.min_height, self.max_height);
+        let after = self.screen_to_world(anchor);
+
+        self.center += before - after;
+    }
+}
+
+impl Camera for PanZoomCamera {
+    fn handle_input(&mut self, input: &Input, _dt: f32) {
+        let ((dx, dy), pinch) = self.pan_drag.update(input, MouseButton::Left);
+        let pixel = self.pixel_size();
+        self.center += Vec2::new(-dx as f32, dy as f32) * pixel;
+
+        let viewport_center =
+            PhysicalPosition::new(self.viewport.0 as f64 / 2.0, self.viewport.1 as f64 / 2.0);
+
+        let scroll = input.scroll_delta().1;
+        if scroll != 0.0 {
+            let anchor = input.cursor_position().unwrap_or(viewport_center);
+            self.zoom_at(anchor, (1.0 - self.zoom_speed).powf(scroll));
+        }
+        if pinch != 1.0 {
+            self.zoom_at(viewport_center, 1.0 / pinch as f32);
+        }
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.viewport = (width, height);
+    }
+
+    fn view(&self) -> Mat4 {
+        Mat4::from_translation(Vec3::new(-self.center.x, -self.center.y, 0.0))
+    }
+
+    fn projection(&self) -> Mat4 {
+        let (half_width, half_height) = (self.width() / 2.0, self.height / 2.0);
+
+        orthographic(
+            -half_width,
+            half_width,
+            -half_height,
+            half_height,
+            -1.0,
+            1.0,
+        )
+    }
+}
@@ -1,19 +1,47 @@
 use std::sync::Arc;
 
+use web_time::{Duration, Instant};
 use winit::dpi::PhysicalSize;
-use winit::event_loop::ActiveEventLoop;
-use winit::window::{Window, WindowAttributes};
+use winit::event_loop::{ActiveEventLoop, ControlFlow};
+use winit::window::{Window, WindowAttributes, WindowId};
 
 #[cfg(target_arch = "wasm32")]
 use winit::platform::web::WindowAttributesExtWebSys;
 
+use crate::app::{RedrawMode, TARGET_FPS_RANGE};
+use crate::gpu::GpuContext;
+use crate::input::Input;
+use crate::overlay::Overlay;
+use crate::std::target::RenderTarget;
+use crate::time::FrameTiming;
+
 pub struct Display<'a> {
     pub window: Arc<Window>,
     pub size: winit::dpi::PhysicalSize<u32>,
-    pub surface: wgpu::Surface<'a>,
+    /// `None` while the app is suspended, see [`Display::surface`].
+    pub surface: Option<wgpu::Surface<'a>>,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
+    pub input: Input,
+    pub overlay: Option<Box<dyn Overlay>>,
+    pub gpu: Arc<GpuContext>,
+    pub timing: FrameTiming,
+    pub(crate) occluded: bool,
+    pub(crate) minimized: bool,
+    pub(crate) redraw_deadline: Option<Instant>,
+    pub(crate) requests: Vec<AppRequest>,
+}
+
+/// Changes asked for by the app, carried out by [`crate::app::App`] after the current callback
+/// returns.
+pub(crate) enum AppRequest {
+    Open(Box<WindowAttributes>),
+    Close(WindowId),
+    ControlFlow(ControlFlow),
+    RedrawMode(RedrawMode),
+    TargetFps(Option<f32>),
+    Exit,
 }
 
 impl<'a> Display<'a> {
@@ -21,11 +49,9 @@ impl<'a> Display<'a> {
         event_loop: &ActiveEventLoop,
         window_size: PhysicalSize<u32>,
     ) -> Window {
-        let window = event_loop
+        event_loop
             .create_window(WindowAttributes::default().with_inner_size(window_size))
-            .expect("Couldn't create window");
-
-        window
+            .expect("Couldn't create window")
     }
 
     #[cfg(target_arch = "wasm32")]
@@ -33,58 +59,45 @@ impl<'a> Display<'a> {
         event_loop: &ActiveEventLoop,
         canvas: wgpu::web_sys::HtmlCanvasElement,
     ) -> Window {
-        let window = event_loop
+        event_loop
             .create_window(WindowAttributes::default().with_canvas(Some(canvas)))
-            .expect("Couldn't create window");
-
-        window
+            .expect("Couldn't create window")
     }
 
+    /// Creates a display with its own [`GpuContext`], picking an adapter that can present to
+    /// the window.
     pub fn from_window(window: Window) -> Self {
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            #[cfg(not(target_arch = "wasm32"))]
-            backends: wgpu::Backends::PRIMARY,
-            #[cfg(target_arch = "wasm32")]
-            backends: wgpu::Backends::GL,
-            ..Default::default()
-        });
+        let instance = GpuContext::create_instance();
 
         let window = Arc::new(window);
         let surface = instance
             .create_surface(Arc::clone(&window))
             .expect("Couldn't create surface");
 
-        let (adapter, device, queue) = pollster::block_on(async {
-            let adapter = instance
-                .request_adapter(&wgpu::RequestAdapterOptions {
-                    power_preference: wgpu::PowerPreference::default(),
-                    compatible_surface: Some(&surface),
-                    force_fallback_adapter: false,
-                })
-                .await
-                .expect("Couldn't get adapter");
-
-            let (device, queue) = adapter
-                .request_device(&wgpu::DeviceDescriptor {
-                    label: None,
-                    required_features: wgpu::Features::empty(),
-                    required_limits: if cfg!(target_arch = "wasm32") {
-                        wgpu::Limits::downlevel_webgl2_defaults()
-                    } else {
-                        wgpu::Limits::default()
-                    },
-                    memory_hints: wgpu::MemoryHints::Performance,
-                    trace: wgpu::Trace::Off,
-                })
-                .await
-                .unwrap();
-
-            (adapter, device, queue)
-        });
+        let gpu = pollster::block_on(GpuContext::request(instance, Some(&surface)));
+        Self::from_surface(window, surface, gpu)
+    }
+
+    /// Creates a display that renders with an existing [`GpuContext`].
+    pub fn with_context(window: Window, gpu: Arc<GpuContext>) -> Display<'static> {
+        let window = Arc::new(window);
+        let surface = gpu
+            .instance
+            .create_surface(Arc::clone(&window))
+            .expect("Couldn't create surface");
+
+        Display::from_surface(window, surface, gpu)
+    }
 
+    /// Creates a display for another window that shares this display's [`GpuContext`].
+    pub fn share(&self, window: Window) -> Display<'static> {
+        Display::with_context(window, Arc::clone(&self.gpu))
+    }
+
+    fn from_surface(window: Arc<Window>, surface: wgpu::Surface<'a>, gpu: Arc<GpuContext>) -> Self {
         let size = window.inner_size();
 
-        let surface_caps = surface.get_capabilities(&adapter);
+        let surface_caps = surface.get_capabilities(&gpu.adapter);
         let surface_format = surface_caps
             .formats
             .iter()
@@ -105,23 +118,149 @@ impl<'a> Display<'a> {
         Self {
             window,
             size,
-            surface,
-            device,
-            queue,
+            surface: Some(surface),
+            device: gpu.device.clone(),
+            queue: gpu.queue.clone(),
             config,
+            input: Input::default(),
+            overlay: None,
+            gpu,
+            timing: FrameTiming::default(),
+            occluded: false,
+            minimized: false,
+            redraw_deadline: None,
+            requests: Vec::new(),
         }
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        self.minimized = new_size.width == 0 || new_size.height == 0;
         if new_size.width > 0 && new_size.height > 0 {
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
+            if let Some(surface) = &self.surface {
+                surface.configure(&self.device, &self.config);
+            }
+        }
+    }
+
+    /// The window's surface. Only missing while the app is suspended, when nothing is drawn.
+    pub fn surface(&self) -> &wgpu::Surface<'a> {
+        self.surface
+            .as_ref()
+            .expect("The surface is dropped while the app is suspended")
+    }
+
+    /// Drops the surface, for platforms that destroy the window's native surface while the app
+    /// is suspended.
+    pub(crate) fn drop_surface(&mut self) {
+        self.surface = None;
+    }
+
+    /// Replaces the surface, e.g. after it was dropped while the app was suspended.
+    pub fn recreate_surface(&mut self) {
+        let surface = self
+            .gpu
+            .instance
+            .create_surface(Arc::clone(&self.window))
+            .expect("Couldn't create surface");
+        if self.config.width > 0 && self.config.height > 0 {
+            surface.configure(&self.device, &self.config);
         }
+        self.surface = Some(surface);
     }
 
     pub fn window(&self) -> &Window {
         &self.window
     }
+
+    /// Whether the window can be seen, i.e. it is neither minimized nor occluded. Redraws are
+    /// paused while it can't.
+    pub fn is_visible(&self) -> bool {
+        !self.occluded && !self.minimized
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    /// Opens another window sharing this display's device once the current callback returns.
+    /// Its display is passed to [`crate::app::AppState::window_opened`].
+    pub fn open_window(&mut self, attributes: WindowAttributes) {
+        self.requests.push(AppRequest::Open(Box::new(attributes)));
+    }
+
+    /// Closes the window with the given id. Closing the main window exits the app.
+    pub fn close_window(&mut self, id: WindowId) {
+        self.requests.push(AppRequest::Close(id));
+    }
+
+    /// Exits the app once the current callback returns, after [`crate::app::AppState::on_exit`].
+    pub fn exit(&mut self) {
+        self.requests.push(AppRequest::Exit);
+    }
+
+    /// Switches the event loop between polling and waiting. With [`ControlFlow::Wait`] frames
+    /// are only drawn when the platform or [`Display::request_redraw`] asks for one; with
+    /// [`ControlFlow::WaitUntil`] all windows are also redrawn once the deadline passes, after
+    /// which the control flow goes back to [`ControlFlow::Wait`].
+    pub fn set_control_flow(&mut self, control_flow: ControlFlow) {
+        self.requests.push(AppRequest::ControlFlow(control_flow));
+    }
+
+    /// Switches between drawing every frame and drawing only when something changed.
+    pub fn set_redraw_mode(&mut self, mode: RedrawMode) {
+        self.requests.push(AppRequest::RedrawMode(mode));
+    }
+
+    /// Limits [`RedrawMode::Continuous`] to `fps` frames per second, or lifts the limit with
+    /// `None`. The achieved rate is reported in [`Display::timing`]. Rates outside
+    /// [`TARGET_FPS_RANGE`], including NaN and infinity, are ignored with a warning.
+    pub fn set_target_fps(&mut self, fps: Option<f32>) {
+        if let Some(fps) = fps.filter(|fps| !TARGET_FPS_RANGE.contains(fps)) {
+            log::warn!(
+                "Ignoring target fps {}, expected a value in {:?}",
+                fps,
+                TARGET_FPS_RANGE
+            );
+            return;
+        }
+        self.requests.push(AppRequest::TargetFps(fps));
+    }
+
+    pub fn request_redraw(&self) {
+        self.window.request_redraw();
+    }
+
+    /// Marks the window as needing a new frame, for changes that don't come from its own input.
+    pub fn mark_dirty(&self) {
+        self.request_redraw();
+    }
+
+    /// Redraws the window no later than `deadline`, e.g. for the next step of an animation in
+    /// [`RedrawMode::OnDemand`]. The earliest pending deadline wins.
+    pub fn redraw_at(&mut self, deadline: Instant) {
+        self.redraw_deadline = Some(match self.redraw_deadline {
+            Some(current) => current.min(deadline),
+            None => deadline,
+        });
+    }
+
+    pub fn redraw_after(&mut self, delay: Duration) {
+        self.redraw_at(Instant::now() + delay);
+    }
+
+    /// Draws the overlay, if there is one, on top of `target`.
+    pub fn draw_overlay(&mut self, encoder: &mut wgpu::CommandEncoder, target: &RenderTarget) {
+        if let Some(mut overlay) = self.overlay.take() {
+            overlay.render(self, encoder, target);
+            self.overlay = Some(overlay);
+        }
+    }
+
+    /// The overlay, if it is a `T`.
+    pub fn overlay_mut<T: Overlay>(&mut self) -> Option<&mut T> {
+        self.overlay.as_mut()?.as_any_mut().downcast_mut()
+    }
 }
@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+/// The wgpu instance, adapter, device and queue. Created once and shared by every
+/// [`crate::display::Display`] and offscreen target, so resources work with all of them.
+#[derive(Clone)]
+pub struct GpuContext {
+    pub instance: wgpu::Instance,
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub adapter_info: wgpu::AdapterInfo,
+}
+
+impl GpuContext {
+    pub fn create_instance() -> wgpu::Instance {
+        wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            #[cfg(not(target_arch = "wasm32"))]
+            backends: wgpu::Backends::PRIMARY,
+            #[cfg(target_arch = "wasm32")]
+            backends: wgpu::Backends::GL,
+            ..Default::default()
+        })
+    }
+
+    /// Picks an adapter that can present to `compatible_surface`, if given, and opens a device
+    /// on it.
+    pub async fn request(
+        instance: wgpu::Instance,
+        compatible_surface: Option<&wgpu::Surface<'_>>,
+    ) -> Arc<Self> {
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface,
+                force_fallback_adapter: false,
+            })
+            .await
+            .expect("Couldn't get adapter");
+
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: None,
+                required_features: wgpu::Features::empty(),
+                required_limits: if cfg!(target_arch = "wasm32") {
+                    wgpu::Limits::downlevel_webgl2_defaults()
+                } else {
+                    wgpu::Limits::default()
+                },
+                memory_hints: wgpu::MemoryHints::Performance,
+                trace: wgpu::Trace::Off,
+            })
+            .await
+            .unwrap();
+
+        let adapter_info = adapter.get_info();
+        log::info!(
+            "Using {} ({:?}, {:?})",
+            adapter_info.name,
+            adapter_info.backend,
+            adapter_info.device_type
+        );
+
+        Arc::new(Self {
+            instance,
+            adapter,
+            device,
+            queue,
+            adapter_info,
+        })
+    }
+
+    /// A context without any window, for offscreen rendering and compute.
+    pub fn headless() -> Arc<Self> {
+        pollster::block_on(Self::request(Self::create_instance(), None))
+    }
+}
+
+#[cfg(test)]
+impl GpuContext {
+    /// A context on wgpu's noop backend, which validates resources but never touches a GPU.
+    pub(crate) fn noop() -> Arc<Self> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::NOOP,
+            backend_options: wgpu::BackendOptions {
+                noop: wgpu::NoopBackendOptions { enable: true },
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        pollster::block_on(Self::request(instance, None))
+    }
+}
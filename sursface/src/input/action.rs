@@ -0,0 +1,579 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+
+use super::Input;
+
+/// A digital input an action can be bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Button {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    /// Counts as pressed on frames where the wheel scrolled in that direction.
+    WheelUp,
+    WheelDown,
+    WheelLeft,
+    WheelRight,
+}
+
+/// An analog input an axis action can be bound to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Axis {
+    /// -1 while `negative` is held, +1 while `positive` is held.
+    Buttons {
+        negative: Button,
+        positive: Button,
+    },
+    /// Raw mouse motion since the last frame.
+    MouseX,
+    MouseY,
+    /// Scrolling since the last frame, in lines.
+    WheelX,
+    WheelY,
+    /// A value fed by the app with [`ActionMap::set_analog`], e.g. a gamepad stick.
+    Analog(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AxisBinding {
+    pub axis: Axis,
+    pub scale: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Named actions bound to keys, mouse buttons, the wheel and analog axes, queried against the
+/// [`Input`] state. Bindings can be changed at runtime and saved as text, one action per line:
+///
+/// ```text
+/// zoom_in = KeyZ | MouseLeft | WheelUp
+/// move_x = KeyA/KeyD | Analog(left_x) | MouseX*0.1
+/// ```
+///
+/// So the text stays readable back, action and analog names can't be empty, start or end with
+/// whitespace, or contain any of `=|#()*/`. Binding such a name panics.
+#[derive(Clone, Debug, Default)]
+pub struct ActionMap {
+    buttons: HashMap<String, Vec<Button>>,
+    axes: HashMap<String, Vec<AxisBinding>>,
+    analog: HashMap<String, f32>,
+}
+
+impl ActionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bind(&mut self, action: &str, button: Button) -> &mut Self {
+        check_name(action);
+        self.buttons
+            .entry(action.to_owned())
+            .or_default()
+            .push(button);
+        self
+    }
+
+    pub fn bind_axis(&mut self, action: &str, axis: Axis, scale: f32) -> &mut Self {
+        check_axis_names(action, &axis);
+        self.axes
+            .entry(action.to_owned())
+            .or_default()
+            .push(AxisBinding { axis, scale });
+        self
+    }
+
+    /// Replaces every binding of `action` with `button`.
+    pub fn rebind(&mut self, action: &str, button: Button) -> &mut Self {
+        check_name(action);
+        self.buttons.insert(action.to_owned(), vec![button]);
+        self
+    }
+
+    pub fn rebind_axis(&mut self, action: &str, axis: Axis, scale: f32) -> &mut Self {
+        check_axis_names(action, &axis);
+        self.axes
+            .insert(action.to_owned(), vec![AxisBinding { axis, scale }]);
+        self
+    }
+
+    pub fn unbind(&mut self, action: &str) {
+        self.buttons.remove(action);
+        self.axes.remove(action);
+    }
+
+    pub fn bindings(&self, action: &str) -> &[Button] {
+        self.buttons.get(action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn axis_bindings(&self, action: &str) -> &[AxisBinding] {
+        self.axes.get(action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn set_analog(&mut self, name: &str, value: f32) {
+        self.analog.insert(name.to_owned(), value);
+    }
+
+    pub fn pressed(&self, input: &Input, action: &str) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|b| button_pressed(input, *b))
+    }
+
+    pub fn just_pressed(&self, input: &Input, action: &str) -> bool {
+        let bindings = self.bindings(action);
+        // another binding already holding the action down means it did not just start
+        bindings.iter().any(|b| button_just_pressed(input, *b))
+            && !bindings
+                .iter()
+                .any(|b| button_pressed(input, *b) && !button_just_pressed(input, *b))
+    }
+
+    pub fn just_released(&self, input: &Input, action: &str) -> bool {
+        let bindings = self.bindings(action);
+        bindings.iter().any(|b| button_just_released(input, *b))
+            && !bindings.iter().any(|b| button_pressed(input, *b))
+    }
+
+    /// The sum of every binding of the axis `action`. Button bindings of an axis action count
+    /// as 1 while held.
+    pub fn axis(&self, input: &Input, action: &str) -> f32 {
+        let buttons: f32 = self
+            .bindings(action)
+            .iter()
+            .filter(|b| button_pressed(input, **b))
+            .count() as f32;
+
+        self.axis_bindings(action)
+            .iter()
+            .map(|binding| self.axis_value(input, &binding.axis) * binding.scale)
+            .sum::<f32>()
+            + buttons
+    }
+
+    fn axis_value(&self, input: &Input, axis: &Axis) -> f32 {
+        match axis {
+            Axis::Buttons { negative, positive } => {
+                button_pressed(input, *positive) as i32 as f32
+                    - button_pressed(input, *negative) as i32 as f32
+            }
+            Axis::MouseX => input.mouse_motion().0 as f32,
+            Axis::MouseY => input.mouse_motion().1 as f32,
+            Axis::WheelX => input.scroll_delta().0,
+            Axis::WheelY => input.scroll_delta().1,
+            Axis::Analog(name) => self.analog.get(name).copied().unwrap_or(0.0),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut lines: Vec<String> = self
+            .buttons
+            .iter()
+            .map(|(action, buttons)| {
+                let bindings: Vec<String> = buttons.iter().map(|b| button_name(*b)).collect();
+                format!("{} = {}", action, bindings.join(" | "))
+            })
+            .chain(self.axes.iter().map(|(action, axes)| {
+                let bindings: Vec<String> = axes.iter().map(axis_name).collect();
+                format!("{} = {}", action, bindings.join(" | "))
+            }))
+            .collect();
+        lines.sort();
+
+        let mut text = lines.join("\n");
+        text.push('\n');
+        text
+    }
+
+    /// Parses bindings written by [`ActionMap::to_text`]. Blank lines and `#` comments are
+    /// ignored. Analog values are not part of the text and start at zero.
+    pub fn from_text(text: &str) -> Result<Self, ParseError> {
+        let mut map = Self::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: String| ParseError {
+                line: index + 1,
+                message,
+            };
+
+            let (action, bindings) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected `action = bindings`".to_owned()))?;
+            let action = action.trim();
+            if action.is_empty() {
+                return Err(error("missing action name".to_owned()));
+            }
+            if !valid_name(action) {
+                return Err(error(format!("invalid action name `{}`", action)));
+            }
+
+            for binding in bindings.split('|').map(str::trim) {
+                if let Some(button) = parse_button(binding) {
+                    map.bind(action, button);
+                } else if let Some(axis) = parse_axis(binding) {
+                    map.axes.entry(action.to_owned()).or_default().push(axis);
+                } else {
+                    return Err(error(format!("unknown binding `{}`", binding)));
+                }
+            }
+        }
+
+        Ok(map)
+    }
+}
+
+/// Characters with a meaning in the text format.
+const RESERVED: &[char] = &['=', '|', '#', '(', ')', '*', '/', '\n', '\r'];
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.trim() == name && !name.contains(RESERVED)
+}
+
+fn check_name(name: &str) {
+    assert!(
+        valid_name(name),
+        "invalid action or analog name {:?}: names can't be empty, start or end with \
+         whitespace, or contain any of `=|#()*/`",
+        name
+    );
+}
+
+fn check_axis_names(action: &str, axis: &Axis) {
+    check_name(action);
+    if let Axis::Analog(name) = axis {
+        check_name(name);
+    }
+}
+
+fn button_pressed(input: &Input, button: Button) -> bool {
+    match button {
+        Button::Key(key) => input.key_pressed(key),
+        Button::Mouse(button) => input.mouse_pressed(button),
+        wheel => wheel_moved(input, wheel),
+    }
+}
+
+fn button_just_pressed(input: &Input, button: Button) -> bool {
+    match button {
+        Button::Key(key) => input.key_just_pressed(key),
+        Button::Mouse(button) => input.mouse_just_pressed(button),
+        wheel => wheel_moved(input, wheel),
+    }
+}
+
+fn button_just_released(input: &Input, button: Button) -> bool {
+    match button {
+        Button::Key(key) => input.key_just_released(key),
+        Button::Mouse(button) => input.mouse_just_released(button),
+        _ => false,
+    }
+}
+
+fn wheel_moved(input: &Input, wheel: Button) -> bool {
+    let (x, y) = input.scroll_delta();
+    match wheel {
+        Button::WheelUp => y > 0.0,
+        Button::WheelDown => y < 0.0,
+        Button::WheelLeft => x > 0.0,
+        Button::WheelRight => x < 0.0,
+        _ => false,
+    }
+}
+
+fn button_name(button: Button) -> String {
+    match button {
+        Button::Key(key) => format!("{:?}", key),
+        Button::Mouse(MouseButton::Left) => "MouseLeft".to_owned(),
+        Button::Mouse(MouseButton::Right) => "MouseRight".to_owned(),
+        Button::Mouse(MouseButton::Middle) => "MouseMiddle".to_owned(),
+        Button::Mouse(MouseButton::Back) => "MouseBack".to_owned(),
+        Button::Mouse(MouseButton::Forward) => "MouseForward".to_owned(),
+        Button::Mouse(MouseButton::Other(n)) => format!("Mouse{}", n),
+        Button::WheelUp => "WheelUp".to_owned(),
+        Button::WheelDown => "WheelDown".to_owned(),
+        Button::WheelLeft => "WheelLeft".to_owned(),
+        Button::WheelRight => "WheelRight".to_owned(),
+    }
+}
+
+fn axis_name(binding: &AxisBinding) -> String {
+    let axis = match &binding.axis {
+        Axis::Buttons { negative, positive } => {
+            format!("{}/{}", button_name(*negative), button_name(*positive))
+        }
+        Axis::MouseX => "MouseX".to_owned(),
+        Axis::MouseY => "MouseY".to_owned(),
+        Axis::WheelX => "WheelX".to_owned(),
+        Axis::WheelY => "WheelY".to_owned(),
+        Axis::Analog(name) => format!("Analog({})", name),
+    };
+
+    if binding.scale == 1.0 {
+        axis
+    } else {
+        format!("{}*{}", axis, binding.scale)
+    }
+}
+
+fn parse_button(name: &str) -> Option<Button> {
+    Some(match name {
+        "MouseLeft" => Button::Mouse(MouseButton::Left),
+        "MouseRight" => Button::Mouse(MouseButton::Right),
+        "MouseMiddle" => Button::Mouse(MouseButton::Middle),
+        "MouseBack" => Button::Mouse(MouseButton::Back),
+        "MouseForward" => Button::Mouse(MouseButton::Forward),
+        "WheelUp" => Button::WheelUp,
+        "WheelDown" => Button::WheelDown,
+        "WheelLeft" => Button::WheelLeft,
+        "WheelRight" => Button::WheelRight,
+        name => match name.strip_prefix("Mouse").map(str::parse::<u16>) {
+            Some(Ok(n)) => Button::Mouse(MouseButton::Other(n)),
+            _ => Button::Key(*KEY_CODES.iter().find(|key| format!("{:?}", key) == name)?),
+        },
+    })
+}
+
+fn parse_axis(binding: &str) -> Option<AxisBinding> {
+    let (axis, scale) = match binding.split_once('*') {
+        Some((axis, scale)) => (axis.trim(), scale.trim().parse().ok()?),
+        None => (binding, 1.0),
+    };
+
+    let axis = match axis {
+        "MouseX" => Axis::MouseX,
+        "MouseY" => Axis::MouseY,
+        "WheelX" => Axis::WheelX,
+        "WheelY" => Axis::WheelY,
+        axis => {
+            if let Some(name) = axis
+                .strip_prefix("Analog(")
+                .and_then(|a| a.strip_suffix(')'))
+                .filter(|name| valid_name(name))
+            {
+                Axis::Analog(name.to_owned())
+            } else {
+                let (negative, positive) = axis.split_once('/')?;
+                Axis::Buttons {
+                    negative: parse_button(negative.trim())?,
+                    positive: parse_button(positive.trim())?,
+                }
+            }
+        }
+    };
+
+    Some(AxisBinding { axis, scale })
+}
+
+macro_rules! key_codes {
+    ($($key:ident),* $(,)?) => {
+        const KEY_CODES: &[KeyCode] = &[$(KeyCode::$key),*];
+    };
+}
+
+key_codes! {
+    Backquote, Backslash, BracketLeft, BracketRight, Comma, Digit0, Digit1, Digit2, Digit3,
+    Digit4, Digit5, Digit6, Digit7, Digit8, Digit9, Equal, IntlBackslash, IntlRo, IntlYen,
+    KeyA, KeyB, KeyC, KeyD, KeyE, KeyF, KeyG, KeyH, KeyI, KeyJ, KeyK, KeyL, KeyM, KeyN, KeyO,
+    KeyP, KeyQ, KeyR, KeyS, KeyT, KeyU, KeyV, KeyW, KeyX, KeyY, KeyZ, Minus, Period, Quote,
+    Semicolon, Slash, AltLeft, AltRight, Backspace, CapsLock, ContextMenu, ControlLeft,
+    ControlRight, Enter, SuperLeft, SuperRight, ShiftLeft, ShiftRight, Space, Tab,
+    Convert, KanaMode, Lang1, Lang2, Lang3, Lang4, Lang5, NonConvert, Delete, End, Help,
+    Home, Insert, PageDown, PageUp, ArrowDown, ArrowLeft, ArrowRight, ArrowUp, NumLock,
+    Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8,
+    Numpad9, NumpadAdd, NumpadBackspace, NumpadClear, NumpadClearEntry, NumpadComma,
+    NumpadDecimal, NumpadDivide, NumpadEnter, NumpadEqual, NumpadHash, NumpadMemoryAdd,
+    NumpadMemoryClear, NumpadMemoryRecall, NumpadMemoryStore, NumpadMemorySubtract,
+    NumpadMultiply, NumpadParenLeft, NumpadParenRight, NumpadStar, NumpadSubtract,
+    Escape, Fn, FnLock, PrintScreen, ScrollLock, Pause, BrowserBack, BrowserFavorites,
+    BrowserForward, BrowserHome, BrowserRefresh, BrowserSearch, BrowserStop, Eject,
+    LaunchApp1, LaunchApp2, LaunchMail, MediaPlayPause, MediaSelect, MediaStop,
+    MediaTrackNext, MediaTrackPrevious, Power, Sleep, AudioVolumeDown, AudioVolumeMute,
+    AudioVolumeUp, WakeUp, Meta, Hyper, Turbo, Abort, Resume, Suspend, Again, Copy, Cut,
+    Find, Open, Paste, Props, Select, Undo, Hiragana, Katakana, F1, F2, F3, F4, F5, F6, F7, F8,
+    F9, F10, F11, F12, F13, F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24, F25, F26, F27,
+    F28, F29, F30, F31, F32, F33, F34, F35,
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::event::{DeviceId, ElementState, MouseScrollDelta, TouchPhase, WindowEvent};
+    use winit::keyboard::{Key, PhysicalKey};
+
+    use super::*;
+
+    fn key(input: &mut Input, code: KeyCode, pressed: bool) {
+        input.handle_key(
+            PhysicalKey::Code(code),
+            Key::Character("x".into()),
+            pressed,
+            false,
+        );
+    }
+
+    fn example() -> ActionMap {
+        let mut map = ActionMap::new();
+        map.bind("zoom in", Button::Key(KeyCode::KeyZ))
+            .bind("zoom in", Button::Mouse(MouseButton::Other(5)))
+            .bind("zoom in", Button::WheelUp)
+            .bind_axis(
+                "move_x",
+                Axis::Buttons {
+                    negative: Button::Key(KeyCode::KeyA),
+                    positive: Button::Mouse(MouseButton::Right),
+                },
+                1.0,
+            )
+            .bind_axis("move_x", Axis::Analog("left_x".to_owned()), -2.5)
+            .bind_axis("move_x", Axis::MouseX, 0.1)
+            .bind_axis("scroll", Axis::WheelY, 1.0);
+        map
+    }
+
+    #[test]
+    fn text_round_trips() {
+        let map = example();
+        let text = map.to_text();
+        assert_eq!(
+            text,
+            "move_x = KeyA/MouseRight | Analog(left_x)*-2.5 | MouseX*0.1\n\
+             scroll = WheelY\n\
+             zoom in = KeyZ | Mouse5 | WheelUp\n"
+        );
+
+        let parsed = ActionMap::from_text(&text).unwrap();
+        assert_eq!(parsed.to_text(), text);
+        for action in ["zoom in", "move_x", "scroll"] {
+            assert_eq!(parsed.bindings(action), map.bindings(action));
+            assert_eq!(parsed.axis_bindings(action), map.axis_bindings(action));
+        }
+    }
+
+    #[test]
+    fn from_text_skips_comments_and_reports_lines() {
+        let map = ActionMap::from_text("# controls\n\n  jump = Space # and more  \n").unwrap();
+        assert_eq!(map.bindings("jump"), [Button::Key(KeyCode::Space)]);
+
+        let error = |text: &str| {
+            let error = ActionMap::from_text(text).unwrap_err();
+            (error.line, error.message)
+        };
+        assert_eq!(
+            error("jump = Space\njump Space"),
+            (2, "expected `action = bindings`".to_owned())
+        );
+        assert_eq!(error(" = Space"), (1, "missing action name".to_owned()));
+        assert_eq!(
+            error("\njump = KeyQQ"),
+            (2, "unknown binding `KeyQQ`".to_owned())
+        );
+        assert_eq!(
+            error("look = MouseX*fast"),
+            (1, "unknown binding `MouseX*fast`".to_owned())
+        );
+        assert_eq!(
+            error("move = KeyA/"),
+            (1, "unknown binding `KeyA/`".to_owned())
+        );
+        assert_eq!(error("jump = "), (1, "unknown binding ``".to_owned()));
+        assert_eq!(
+            error("jump (high) = Space"),
+            (1, "invalid action name `jump (high)`".to_owned())
+        );
+        assert_eq!(
+            error("move = Analog(a/b)"),
+            (1, "unknown binding `Analog(a/b)`".to_owned())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid action or analog name")]
+    fn bind_rejects_reserved_characters() {
+        ActionMap::new().bind("jump = now", Button::WheelUp);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid action or analog name")]
+    fn bind_axis_rejects_reserved_analog_names() {
+        ActionMap::new().bind_axis("move", Axis::Analog("stick)".to_owned()), 1.0);
+    }
+
+    #[test]
+    fn just_pressed_and_released_cover_every_binding() {
+        let mut map = ActionMap::new();
+        map.bind("fire", Button::Key(KeyCode::Space))
+            .bind("fire", Button::Mouse(MouseButton::Left));
+        let mut input = Input::default();
+
+        key(&mut input, KeyCode::Space, true);
+        assert!(map.pressed(&input, "fire"));
+        assert!(map.just_pressed(&input, "fire"));
+        input.end_frame();
+
+        // the second binding doesn't press an action that is already down
+        input.handle_window_event(&WindowEvent::MouseInput {
+            device_id: DeviceId::dummy(),
+            state: ElementState::Pressed,
+            button: MouseButton::Left,
+        });
+        assert!(!map.just_pressed(&input, "fire"));
+        input.end_frame();
+
+        key(&mut input, KeyCode::Space, false);
+        assert!(map.pressed(&input, "fire"));
+        assert!(!map.just_released(&input, "fire"));
+        input.end_frame();
+
+        input.handle_window_event(&WindowEvent::MouseInput {
+            device_id: DeviceId::dummy(),
+            state: ElementState::Released,
+            button: MouseButton::Left,
+        });
+        assert!(!map.pressed(&input, "fire"));
+        assert!(map.just_released(&input, "fire"));
+        assert!(!map.pressed(&input, "unbound"));
+    }
+
+    #[test]
+    fn axes_sum_their_bindings() {
+        let mut map = example();
+        map.bind("scroll", Button::Key(KeyCode::ShiftLeft));
+        let mut input = Input::default();
+        assert_eq!(map.axis(&input, "move_x"), 0.0);
+
+        key(&mut input, KeyCode::KeyA, true);
+        map.set_analog("left_x", 0.5);
+        input.handle_device_event(&winit::event::DeviceEvent::MouseMotion { delta: (10.0, 3.0) });
+        assert_eq!(map.axis(&input, "move_x"), -1.0 - 1.25 + 1.0);
+
+        // buttons bound to an axis action count as 1 while held
+        key(&mut input, KeyCode::ShiftLeft, true);
+        input.handle_window_event(&WindowEvent::MouseWheel {
+            device_id: DeviceId::dummy(),
+            delta: MouseScrollDelta::LineDelta(0.0, 2.0),
+            phase: TouchPhase::Moved,
+        });
+        assert_eq!(map.axis(&input, "scroll"), 3.0);
+        assert!(map.pressed(&input, "zoom in"));
+
+        input.end_frame();
+        assert!(!map.pressed(&input, "zoom in"));
+        assert_eq!(map.axis(&input, "scroll"), 1.0);
+    }
+}
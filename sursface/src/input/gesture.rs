@@ -0,0 +1,715 @@
+use winit::dpi::PhysicalPosition;
+use winit::event::{ElementState, MouseButton, TouchPhase, WindowEvent};
+
+use crate::time::now_secs_f64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GestureConfig {
+    /// Longest press, in seconds, that still counts as a tap.
+    pub tap_max_duration: f64,
+    /// Longest gap, in seconds, between a tap and the next press for them to chain.
+    pub multi_tap_interval: f64,
+    /// How long, in seconds, a pointer has to be held still to trigger a long press.
+    pub long_press_duration: f64,
+    /// Distance, in pixels, a pointer has to travel before a press becomes a drag.
+    pub drag_threshold: f64,
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        Self {
+            tap_max_duration: 0.3,
+            multi_tap_interval: 1.0,
+            long_press_duration: 1.0,
+            drag_threshold: 8.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Gesture {
+    /// A pointer went down. `tap_count` is 2 when it follows a tap, 3 after a double tap, etc.
+    Press {
+        position: PhysicalPosition<f64>,
+        tap_count: u32,
+    },
+    /// The last pointer went up.
+    Release {
+        position: PhysicalPosition<f64>,
+    },
+    Tap {
+        position: PhysicalPosition<f64>,
+    },
+    DoubleTap {
+        position: PhysicalPosition<f64>,
+    },
+    LongPress {
+        position: PhysicalPosition<f64>,
+    },
+    DragStart {
+        position: PhysicalPosition<f64>,
+    },
+    Drag {
+        position: PhysicalPosition<f64>,
+        delta: (f64, f64),
+    },
+    DragEnd {
+        position: PhysicalPosition<f64>,
+    },
+    /// Two-finger pinch; `scale` is the change in finger distance since the last event.
+    Pinch {
+        center: PhysicalPosition<f64>,
+        scale: f64,
+    },
+    /// Two-finger rotation; `angle` is the change in radians since the last event.
+    Rotate {
+        center: PhysicalPosition<f64>,
+        angle: f64,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PointerId {
+    Mouse,
+    Touch(u64),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Pointer {
+    id: PointerId,
+    position: PhysicalPosition<f64>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum State {
+    Idle,
+    Pressed {
+        start: PhysicalPosition<f64>,
+        last: PhysicalPosition<f64>,
+        pressed_at: f64,
+        tap_count: u32,
+        /// Set once the press can no longer become a tap or long press.
+        settled: bool,
+        dragging: bool,
+    },
+    MultiTouch {
+        distance: f64,
+        angle: f64,
+    },
+}
+
+/// Turns mouse and touch events into high-level gestures. Feed it every window event, call
+/// [`GestureRecognizer::update`] once per frame for time-based gestures, then drain the result.
+///
+/// The `_at` variants take the current time explicitly, so sequences can be replayed offline.
+pub struct GestureRecognizer {
+    pub config: GestureConfig,
+    pointers: Vec<Pointer>,
+    cursor_position: PhysicalPosition<f64>,
+    state: State,
+    last_tap: Option<(f64, u32)>,
+    gestures: Vec<Gesture>,
+}
+
+impl Default for GestureRecognizer {
+    fn default() -> Self {
+        Self::new(GestureConfig::default())
+    }
+}
+
+impl GestureRecognizer {
+    pub fn new(config: GestureConfig) -> Self {
+        Self {
+            config,
+            pointers: Vec::new(),
+            cursor_position: PhysicalPosition::new(0.0, 0.0),
+            state: State::Idle,
+            last_tap: None,
+            gestures: Vec::new(),
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        self.handle_event_at(event, now_secs_f64());
+    }
+
+    pub fn handle_event_at(&mut self, event: &WindowEvent, now: f64) {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = *position;
+                self.pointer_moved(PointerId::Mouse, *position);
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => match state {
+                ElementState::Pressed => {
+                    self.pointer_down(PointerId::Mouse, self.cursor_position, now)
+                }
+                ElementState::Released => {
+                    self.pointer_up(PointerId::Mouse, self.cursor_position, now)
+                }
+            },
+            WindowEvent::Touch(touch) => {
+                let id = PointerId::Touch(touch.id);
+                match touch.phase {
+                    TouchPhase::Started => self.pointer_down(id, touch.location, now),
+                    TouchPhase::Moved => self.pointer_moved(id, touch.location),
+                    TouchPhase::Ended | TouchPhase::Cancelled => {
+                        self.pointer_up(id, touch.location, now)
+                    }
+                }
+            }
+            // releases may never arrive, so drop whatever was in progress
+            WindowEvent::CursorLeft { .. } => self.cancel_pointer(PointerId::Mouse, now),
+            WindowEvent::Focused(false) => self.cancel(),
+            _ => (),
+        }
+    }
+
+    pub fn update(&mut self) {
+        self.update_at(now_secs_f64());
+    }
+
+    /// Emits gestures that fire after a timeout, like long presses.
+    pub fn update_at(&mut self, now: f64) {
+        if let State::Pressed {
+            last,
+            pressed_at,
+            settled: ref mut settled @ false,
+            ..
+        } = self.state
+        {
+            if now - pressed_at >= self.config.long_press_duration {
+                *settled = true;
+                self.gestures.push(Gesture::LongPress { position: last });
+            }
+        }
+    }
+
+    pub fn drain(&mut self) -> std::vec::Drain<'_, Gesture> {
+        self.gestures.drain(..)
+    }
+
+    /// True while at least one pointer is down.
+    pub fn is_pressed(&self) -> bool {
+        !self.pointers.is_empty()
+    }
+
+    fn pointer_down(&mut self, id: PointerId, position: PhysicalPosition<f64>, now: f64) {
+        if self.pointers.iter().any(|p| p.id == id) {
+            return;
+        }
+        self.pointers.push(Pointer { id, position });
+
+        match self.pointers.len() {
+            1 => {
+                let tap_count = match self.last_tap {
+                    Some((tapped_at, count))
+                        if now - tapped_at <= self.config.multi_tap_interval =>
+                    {
+                        count + 1
+                    }
+                    _ => 1,
+                };
+                self.state = State::Pressed {
+                    start: position,
+                    last: position,
+                    pressed_at: now,
+                    tap_count,
+                    settled: false,
+                    dragging: false,
+                };
+                self.gestures.push(Gesture::Press {
+                    position,
+                    tap_count,
+                });
+            }
+            2 => {
+                if let State::Pressed {
+                    dragging: true,
+                    last,
+                    ..
+                } = self.state
+                {
+                    self.gestures.push(Gesture::DragEnd { position: last });
+                }
+                self.last_tap = None;
+                let (_, distance, angle) = self.two_finger_metrics();
+                self.state = State::MultiTouch { distance, angle };
+            }
+            _ => (),
+        }
+    }
+
+    fn pointer_moved(&mut self, id: PointerId, position: PhysicalPosition<f64>) {
+        let Some(pointer) = self.pointers.iter_mut().find(|p| p.id == id) else {
+            return;
+        };
+        pointer.position = position;
+
+        match self.state {
+            State::Idle => (),
+            State::Pressed {
+                start,
+                ref mut last,
+                ref mut settled,
+                ref mut dragging,
+                ..
+            } => {
+                if !*dragging {
+                    if distance(start, position) < self.config.drag_threshold {
+                        return;
+                    }
+                    *dragging = true;
+                    *settled = true;
+                    self.gestures.push(Gesture::DragStart { position: start });
+                }
+
+                let delta = (position.x - last.x, position.y - last.y);
+                *last = position;
+                self.gestures.push(Gesture::Drag { position, delta });
+            }
+            State::MultiTouch {
+                distance: ref mut last_distance,
+                angle: ref mut last_angle,
+            } => {
+                let (center, distance, angle) = two_finger_metrics(&self.pointers);
+
+                if *last_distance > 0.0 && distance != *last_distance {
+                    self.gestures.push(Gesture::Pinch {
+                        center,
+                        scale: distance / *last_distance,
+                    });
+                }
+                let mut delta = angle - *last_angle;
+                if delta > std::f64::consts::PI {
+                    delta -= std::f64::consts::TAU;
+                } else if delta < -std::f64::consts::PI {
+                    delta += std::f64::consts::TAU;
+                }
+                if delta != 0.0 {
+                    self.gestures.push(Gesture::Rotate {
+                        center,
+                        angle: delta,
+                    });
+                }
+
+                *last_distance = distance;
+                *last_angle = angle;
+            }
+        }
+    }
+
+    fn pointer_up(&mut self, id: PointerId, position: PhysicalPosition<f64>, now: f64) {
+        let Some(index) = self.pointers.iter().position(|p| p.id == id) else {
+            return;
+        };
+        self.pointers.remove(index);
+
+        match (self.state, self.pointers.len()) {
+            (
+                State::Pressed {
+                    pressed_at,
+                    tap_count,
+                    settled,
+                    dragging,
+                    ..
+                },
+                0,
+            ) => {
+                self.gestures.push(Gesture::Release { position });
+
+                if dragging {
+                    self.gestures.push(Gesture::DragEnd { position });
+                }
+
+                if !settled && now - pressed_at <= self.config.tap_max_duration {
+                    self.gestures.push(Gesture::Tap { position });
+                    if tap_count == 2 {
+                        self.gestures.push(Gesture::DoubleTap { position });
+                    }
+                    self.last_tap = Some((now, tap_count));
+                } else {
+                    self.last_tap = None;
+                }
+
+                self.state = State::Idle;
+            }
+            (State::MultiTouch { .. }, 1) => {
+                // the remaining finger can keep dragging, but no longer taps
+                let remaining = self.pointers[0].position;
+                self.state = State::Pressed {
+                    start: remaining,
+                    last: remaining,
+                    pressed_at: now,
+                    tap_count: 0,
+                    settled: true,
+                    dragging: false,
+                };
+            }
+            (State::MultiTouch { .. }, n) if n >= 2 => {
+                let (_, distance, angle) = self.two_finger_metrics();
+                self.state = State::MultiTouch { distance, angle };
+            }
+            (_, 0) => {
+                self.gestures.push(Gesture::Release { position });
+                self.state = State::Idle;
+            }
+            _ => (),
+        }
+    }
+
+    /// Lifts one pointer without producing a tap, leaving gestures of the others running.
+    fn cancel_pointer(&mut self, id: PointerId, now: f64) {
+        let Some(pointer) = self.pointers.iter().find(|p| p.id == id) else {
+            return;
+        };
+        if self.pointers.len() == 1 {
+            self.cancel();
+        } else {
+            // with other pointers down this is a multi-touch, which never taps
+            self.pointer_up(id, pointer.position, now);
+        }
+    }
+
+    /// Lifts every pointer without producing taps.
+    fn cancel(&mut self) {
+        if let Some(pointer) = self.pointers.last() {
+            self.gestures.push(Gesture::Release {
+                position: pointer.position,
+            });
+        }
+        if let State::Pressed {
+            dragging: true,
+            last,
+            ..
+        } = self.state
+        {
+            self.gestures.push(Gesture::DragEnd { position: last });
+        }
+        self.pointers.clear();
+        self.state = State::Idle;
+        self.last_tap = None;
+    }
+
+    fn two_finger_metrics(&self) -> (PhysicalPosition<f64>, f64, f64) {
+        two_finger_metrics(&self.pointers)
+    }
+}
+
+fn distance(a: PhysicalPosition<f64>, b: PhysicalPosition<f64>) -> f64 {
+    ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt()
+}
+
+/// Center, distance and angle between the first two pointers.
+fn two_finger_metrics(pointers: &[Pointer]) -> (PhysicalPosition<f64>, f64, f64) {
+    let (a, b) = (pointers[0].position, pointers[1].position);
+    let center = PhysicalPosition::new((a.x + b.x) / 2.0, (a.y + b.y) / 2.0);
+
+    (center, distance(a, b), (b.y - a.y).atan2(b.x - a.x))
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::event::{DeviceId, Touch};
+
+    use super::*;
+
+    fn touch(id: u64, phase: TouchPhase, x: f64, y: f64) -> WindowEvent {
+        WindowEvent::Touch(Touch {
+            device_id: DeviceId::dummy(),
+            phase,
+            location: PhysicalPosition::new(x, y),
+            force: None,
+            id,
+        })
+    }
+
+    fn cursor(x: f64, y: f64) -> WindowEvent {
+        WindowEvent::CursorMoved {
+            device_id: DeviceId::dummy(),
+            position: PhysicalPosition::new(x, y),
+        }
+    }
+
+    fn mouse(state: ElementState) -> WindowEvent {
+        WindowEvent::MouseInput {
+            device_id: DeviceId::dummy(),
+            state,
+            button: MouseButton::Left,
+        }
+    }
+
+    fn at(x: f64, y: f64) -> PhysicalPosition<f64> {
+        PhysicalPosition::new(x, y)
+    }
+
+    fn drain(recognizer: &mut GestureRecognizer) -> Vec<Gesture> {
+        recognizer.drain().collect()
+    }
+
+    #[test]
+    fn quick_press_is_a_tap() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.handle_event_at(&touch(0, TouchPhase::Started, 1.0, 2.0), 0.0);
+        recognizer.handle_event_at(&touch(0, TouchPhase::Ended, 1.0, 2.0), 0.3);
+        assert_eq!(
+            drain(&mut recognizer),
+            [
+                Gesture::Press {
+                    position: at(1.0, 2.0),
+                    tap_count: 1
+                },
+                Gesture::Release {
+                    position: at(1.0, 2.0)
+                },
+                Gesture::Tap {
+                    position: at(1.0, 2.0)
+                },
+            ]
+        );
+
+        recognizer.handle_event_at(&touch(0, TouchPhase::Started, 1.0, 2.0), 1.3);
+        recognizer.handle_event_at(&touch(0, TouchPhase::Ended, 1.0, 2.0), 1.4);
+        let gestures = drain(&mut recognizer);
+        assert_eq!(
+            gestures[0],
+            Gesture::Press {
+                position: at(1.0, 2.0),
+                tap_count: 2
+            }
+        );
+        assert!(gestures.contains(&Gesture::DoubleTap {
+            position: at(1.0, 2.0)
+        }));
+    }
+
+    #[test]
+    fn slow_press_is_not_a_tap() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.handle_event_at(&touch(0, TouchPhase::Started, 0.0, 0.0), 0.0);
+        recognizer.handle_event_at(&touch(0, TouchPhase::Ended, 0.0, 0.0), 0.31);
+        assert!(!drain(&mut recognizer)
+            .iter()
+            .any(|g| matches!(g, Gesture::Tap { .. })));
+
+        // too late to chain with a previous tap
+        recognizer.handle_event_at(&touch(0, TouchPhase::Started, 0.0, 0.0), 1.0);
+        recognizer.handle_event_at(&touch(0, TouchPhase::Ended, 0.0, 0.0), 1.1);
+        recognizer.handle_event_at(&touch(0, TouchPhase::Started, 0.0, 0.0), 2.2);
+        assert_eq!(
+            drain(&mut recognizer).last(),
+            Some(&Gesture::Press {
+                position: at(0.0, 0.0),
+                tap_count: 1
+            })
+        );
+    }
+
+    #[test]
+    fn held_press_is_a_long_press() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.handle_event_at(&touch(0, TouchPhase::Started, 3.0, 4.0), 0.0);
+        recognizer.update_at(0.99);
+        assert_eq!(drain(&mut recognizer).len(), 1);
+
+        recognizer.update_at(1.0);
+        recognizer.update_at(2.0);
+        assert_eq!(
+            drain(&mut recognizer),
+            [Gesture::LongPress {
+                position: at(3.0, 4.0)
+            }]
+        );
+
+        recognizer.handle_event_at(&touch(0, TouchPhase::Ended, 3.0, 4.0), 2.5);
+        assert_eq!(
+            drain(&mut recognizer),
+            [Gesture::Release {
+                position: at(3.0, 4.0)
+            }]
+        );
+    }
+
+    #[test]
+    fn drag_starts_past_threshold() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.handle_event_at(&cursor(0.0, 0.0), 0.0);
+        recognizer.handle_event_at(&mouse(ElementState::Pressed), 0.0);
+        recognizer.handle_event_at(&cursor(7.9, 0.0), 0.05);
+        assert_eq!(drain(&mut recognizer).len(), 1);
+
+        recognizer.handle_event_at(&cursor(10.0, 0.0), 0.1);
+        assert_eq!(
+            drain(&mut recognizer),
+            [
+                Gesture::DragStart {
+                    position: at(0.0, 0.0)
+                },
+                Gesture::Drag {
+                    position: at(10.0, 0.0),
+                    delta: (10.0, 0.0)
+                },
+            ]
+        );
+
+        // no long press or tap once dragging
+        recognizer.update_at(2.0);
+        recognizer.handle_event_at(&mouse(ElementState::Released), 0.2);
+        assert_eq!(
+            drain(&mut recognizer),
+            [
+                Gesture::Release {
+                    position: at(10.0, 0.0)
+                },
+                Gesture::DragEnd {
+                    position: at(10.0, 0.0)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn two_fingers_pinch_and_rotate() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.handle_event_at(&touch(0, TouchPhase::Started, 0.0, 0.0), 0.0);
+        recognizer.handle_event_at(&touch(1, TouchPhase::Started, 10.0, 0.0), 0.0);
+        drain(&mut recognizer);
+
+        recognizer.handle_event_at(&touch(1, TouchPhase::Moved, 20.0, 0.0), 0.1);
+        assert_eq!(
+            drain(&mut recognizer),
+            [Gesture::Pinch {
+                center: at(10.0, 0.0),
+                scale: 2.0
+            }]
+        );
+
+        recognizer.handle_event_at(&touch(1, TouchPhase::Moved, 0.0, 20.0), 0.2);
+        assert_eq!(
+            drain(&mut recognizer),
+            [Gesture::Rotate {
+                center: at(0.0, 10.0),
+                angle: std::f64::consts::FRAC_PI_2
+            }]
+        );
+
+        // crossing the -x axis wraps instead of jumping by a full turn
+        recognizer.handle_event_at(&touch(1, TouchPhase::Moved, -20.0, 1.0), 0.3);
+        recognizer.handle_event_at(&touch(1, TouchPhase::Moved, -20.0, -1.0), 0.4);
+        let angle = drain(&mut recognizer)
+            .into_iter()
+            .filter_map(|g| match g {
+                Gesture::Rotate { angle, .. } => Some(angle),
+                _ => None,
+            })
+            .next_back()
+            .unwrap();
+        assert!(angle > 0.0 && angle < 0.2);
+
+        recognizer.handle_event_at(&touch(1, TouchPhase::Ended, -20.0, -1.0), 0.5);
+        recognizer.handle_event_at(&touch(0, TouchPhase::Ended, 0.0, 0.0), 0.6);
+        assert_eq!(
+            drain(&mut recognizer),
+            [Gesture::Release {
+                position: at(0.0, 0.0)
+            }]
+        );
+    }
+
+    #[test]
+    fn focus_loss_cancels() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.handle_event_at(&cursor(0.0, 0.0), 0.0);
+        recognizer.handle_event_at(&mouse(ElementState::Pressed), 0.0);
+        recognizer.handle_event_at(&cursor(20.0, 0.0), 0.05);
+        drain(&mut recognizer);
+
+        recognizer.handle_event_at(&WindowEvent::Focused(false), 0.1);
+        assert_eq!(
+            drain(&mut recognizer),
+            [
+                Gesture::Release {
+                    position: at(20.0, 0.0)
+                },
+                Gesture::DragEnd {
+                    position: at(20.0, 0.0)
+                },
+            ]
+        );
+        assert!(!recognizer.is_pressed());
+
+        // the release that never came doesn't produce anything later
+        recognizer.handle_event_at(&mouse(ElementState::Released), 0.15);
+        assert!(drain(&mut recognizer).is_empty());
+
+        recognizer.handle_event_at(&touch(0, TouchPhase::Started, 0.0, 0.0), 0.2);
+        recognizer.handle_event_at(&WindowEvent::Focused(false), 0.25);
+        recognizer.handle_event_at(&touch(0, TouchPhase::Ended, 0.0, 0.0), 0.3);
+        assert!(!drain(&mut recognizer)
+            .iter()
+            .any(|g| matches!(g, Gesture::Tap { .. })));
+        assert!(!recognizer.is_pressed());
+    }
+
+    #[test]
+    fn cursor_leaving_only_cancels_the_mouse() {
+        let cursor_left = WindowEvent::CursorLeft {
+            device_id: DeviceId::dummy(),
+        };
+
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.handle_event_at(&cursor(0.0, 0.0), 0.0);
+        recognizer.handle_event_at(&mouse(ElementState::Pressed), 0.0);
+        drain(&mut recognizer);
+        recognizer.handle_event_at(&cursor_left, 0.1);
+        recognizer.handle_event_at(&mouse(ElementState::Released), 0.15);
+        assert_eq!(
+            drain(&mut recognizer),
+            [Gesture::Release {
+                position: at(0.0, 0.0)
+            }]
+        );
+        assert!(!recognizer.is_pressed());
+
+        // a pinch in progress keeps going
+        recognizer.handle_event_at(&touch(0, TouchPhase::Started, 0.0, 0.0), 1.0);
+        recognizer.handle_event_at(&touch(1, TouchPhase::Started, 10.0, 0.0), 1.0);
+        drain(&mut recognizer);
+        recognizer.handle_event_at(&cursor_left, 1.1);
+        recognizer.handle_event_at(&touch(1, TouchPhase::Moved, 20.0, 0.0), 1.2);
+        assert_eq!(
+            drain(&mut recognizer),
+            [Gesture::Pinch {
+                center: at(10.0, 0.0),
+                scale: 2.0
+            }]
+        );
+
+        // and so does a one-finger drag
+        recognizer.handle_event_at(&touch(1, TouchPhase::Ended, 20.0, 0.0), 1.3);
+        recognizer.handle_event_at(&cursor_left, 1.4);
+        recognizer.handle_event_at(&touch(0, TouchPhase::Moved, 10.0, 0.0), 1.5);
+        recognizer.handle_event_at(&touch(0, TouchPhase::Ended, 10.0, 0.0), 1.6);
+        assert_eq!(
+            drain(&mut recognizer),
+            [
+                Gesture::DragStart {
+                    position: at(0.0, 0.0)
+                },
+                Gesture::Drag {
+                    position: at(10.0, 0.0),
+                    delta: (10.0, 0.0)
+                },
+                Gesture::Release {
+                    position: at(10.0, 0.0)
+                },
+                Gesture::DragEnd {
+                    position: at(10.0, 0.0)
+                },
+            ]
+        );
+    }
+}
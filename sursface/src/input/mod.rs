@@ -0,0 +1,445 @@
+pub mod action;
+pub mod gesture;
+
+use std::collections::{HashMap, HashSet};
+
+use winit::dpi::PhysicalPosition;
+use winit::event::{
+    DeviceEvent, ElementState, MouseButton, MouseScrollDelta, TouchPhase, WindowEvent,
+};
+use winit::keyboard::{Key, KeyCode, ModifiersState, PhysicalKey};
+
+/// Pixels per line when converting precise (touchpad) scrolling into line deltas.
+const PIXELS_PER_LINE: f32 = 20.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TouchPoint {
+    pub id: u64,
+    pub position: PhysicalPosition<f64>,
+    pub start_position: PhysicalPosition<f64>,
+}
+
+/// Keyboard, mouse and touch state, updated by the app from window events and reset after each
+/// frame. "Just pressed" and "just released" queries cover the events since the last frame.
+#[derive(Clone, Debug, Default)]
+pub struct Input {
+    keys: HashSet<KeyCode>,
+    keys_pressed: HashSet<KeyCode>,
+    keys_released: HashSet<KeyCode>,
+    logical_keys: HashSet<Key>,
+    logical_keys_pressed: HashSet<Key>,
+    logical_keys_released: HashSet<Key>,
+    /// The logical key each held physical key produced when pressed, which is what its release
+    /// has to remove even if modifiers changed in between.
+    logical_by_code: HashMap<KeyCode, Key>,
+    buttons: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    buttons_released: HashSet<MouseButton>,
+    cursor_position: Option<PhysicalPosition<f64>>,
+    cursor_delta: (f64, f64),
+    mouse_motion: (f64, f64),
+    scroll_delta: (f32, f32),
+    modifiers: ModifiersState,
+    touches: HashMap<u64, TouchPoint>,
+    /// Touches as they started, so ones that already ended are still reported.
+    touches_started: Vec<TouchPoint>,
+    touches_ended: Vec<TouchPoint>,
+}
+
+impl Input {
+    pub fn key_pressed(&self, key: KeyCode) -> bool {
+        self.keys.contains(&key)
+    }
+
+    pub fn key_just_pressed(&self, key: KeyCode) -> bool {
+        self.keys_pressed.contains(&key)
+    }
+
+    pub fn key_just_released(&self, key: KeyCode) -> bool {
+        self.keys_released.contains(&key)
+    }
+
+    pub fn logical_key_pressed(&self, key: &Key) -> bool {
+        self.logical_keys.contains(key)
+    }
+
+    pub fn logical_key_just_pressed(&self, key: &Key) -> bool {
+        self.logical_keys_pressed.contains(key)
+    }
+
+    pub fn logical_key_just_released(&self, key: &Key) -> bool {
+        self.logical_keys_released.contains(key)
+    }
+
+    pub fn mouse_pressed(&self, button: MouseButton) -> bool {
+        self.buttons.contains(&button)
+    }
+
+    pub fn mouse_just_pressed(&self, button: MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    pub fn mouse_just_released(&self, button: MouseButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+
+    /// `None` while the cursor is outside the window.
+    pub fn cursor_position(&self) -> Option<PhysicalPosition<f64>> {
+        self.cursor_position
+    }
+
+    /// Cursor movement in window pixels since the last frame.
+    pub fn cursor_delta(&self) -> (f64, f64) {
+        self.cursor_delta
+    }
+
+    /// Raw, unaccelerated mouse motion since the last frame, also reported when the cursor is
+    /// grabbed or outside the window.
+    pub fn mouse_motion(&self) -> (f64, f64) {
+        self.mouse_motion
+    }
+
+    /// Scrolling since the last frame, in lines.
+    pub fn scroll_delta(&self) -> (f32, f32) {
+        self.scroll_delta
+    }
+
+    pub fn modifiers(&self) -> ModifiersState {
+        self.modifiers
+    }
+
+    pub fn touches(&self) -> impl Iterator<Item = &TouchPoint> {
+        self.touches.values()
+    }
+
+    pub fn touch(&self, id: u64) -> Option<&TouchPoint> {
+        self.touches.get(&id)
+    }
+
+    /// Touches that started since the last frame, at their current position if they are still
+    /// down. Includes touches that already ended again.
+    pub fn touches_just_started(&self) -> impl Iterator<Item = &TouchPoint> {
+        self.touches_started
+            .iter()
+            .map(|started| self.touches.get(&started.id).unwrap_or(started))
+    }
+
+    /// Touches that ended or were cancelled since the last frame, at their last position.
+    pub fn touches_just_ended(&self) -> &[TouchPoint] {
+        &self.touches_ended
+    }
+
+    pub fn handle_window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardInput { event, .. } => self.handle_key(
+                event.physical_key,
+                event.logical_key.clone(),
+                event.state == ElementState::Pressed,
+                event.repeat,
+            ),
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                update_set(
+                    &mut self.buttons,
+                    &mut self.buttons_pressed,
+                    &mut self.buttons_released,
+                    *button,
+                    *state == ElementState::Pressed,
+                    false,
+                );
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                if let Some(last) = self.cursor_position {
+                    self.cursor_delta.0 += position.x - last.x;
+                    self.cursor_delta.1 += position.y - last.y;
+                }
+                self.cursor_position = Some(*position);
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor_position = None;
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let (x, y) = match *delta {
+                    MouseScrollDelta::LineDelta(x, y) => (x, y),
+                    MouseScrollDelta::PixelDelta(position) => (
+                        position.x as f32 / PIXELS_PER_LINE,
+                        position.y as f32 / PIXELS_PER_LINE,
+                    ),
+                };
+                self.scroll_delta.0 += x;
+                self.scroll_delta.1 += y;
+            }
+            WindowEvent::Touch(touch) => match touch.phase {
+                TouchPhase::Started => {
+                    let point = TouchPoint {
+                        id: touch.id,
+                        position: touch.location,
+                        start_position: touch.location,
+                    };
+                    self.touches.insert(touch.id, point);
+                    self.touches_started.push(point);
+                }
+                TouchPhase::Moved => {
+                    if let Some(point) = self.touches.get_mut(&touch.id) {
+                        point.position = touch.location;
+                    }
+                }
+                TouchPhase::Ended | TouchPhase::Cancelled => {
+                    if let Some(mut point) = self.touches.remove(&touch.id) {
+                        point.position = touch.location;
+                        self.touches_ended.push(point);
+                    }
+                }
+            },
+            WindowEvent::Focused(false) => {
+                // releases are not delivered while unfocused, so nothing can stay held
+                self.keys_released.extend(self.keys.drain());
+                self.logical_keys_released.extend(self.logical_keys.drain());
+                self.logical_by_code.clear();
+                self.buttons_released.extend(self.buttons.drain());
+                self.touches_ended
+                    .extend(self.touches.drain().map(|(_, point)| point));
+                self.modifiers = ModifiersState::empty();
+            }
+            _ => (),
+        }
+    }
+
+    fn handle_key(
+        &mut self,
+        physical_key: PhysicalKey,
+        mut logical_key: Key,
+        pressed: bool,
+        repeat: bool,
+    ) {
+        if let PhysicalKey::Code(code) = physical_key {
+            update_set(
+                &mut self.keys,
+                &mut self.keys_pressed,
+                &mut self.keys_released,
+                code,
+                pressed,
+                repeat,
+            );
+        }
+        if let PhysicalKey::Code(code) = physical_key {
+            if pressed {
+                if !repeat {
+                    self.logical_by_code.insert(code, logical_key.clone());
+                }
+            } else if let Some(key) = self.logical_by_code.remove(&code) {
+                logical_key = key;
+            }
+        }
+        update_set(
+            &mut self.logical_keys,
+            &mut self.logical_keys_pressed,
+            &mut self.logical_keys_released,
+            logical_key,
+            pressed,
+            repeat,
+        );
+    }
+
+    pub fn handle_device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta } = event {
+            self.mouse_motion.0 += delta.0;
+            self.mouse_motion.1 += delta.1;
+        }
+    }
+
+    /// Clears per-frame state. Called by the app after every drawn frame.
+    pub fn end_frame(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.logical_keys_pressed.clear();
+        self.logical_keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.cursor_delta = (0.0, 0.0);
+        self.mouse_motion = (0.0, 0.0);
+        self.scroll_delta = (0.0, 0.0);
+        self.touches_started.clear();
+        self.touches_ended.clear();
+    }
+}
+
+fn update_set<T: Eq + std::hash::Hash + Clone>(
+    held: &mut HashSet<T>,
+    pressed: &mut HashSet<T>,
+    released: &mut HashSet<T>,
+    value: T,
+    is_pressed: bool,
+    repeat: bool,
+) {
+    if is_pressed {
+        if !repeat && held.insert(value.clone()) {
+            pressed.insert(value);
+        }
+    } else if held.remove(&value) {
+        released.insert(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::event::{DeviceId, Touch};
+    use winit::keyboard::{NamedKey, SmolStr};
+
+    use super::*;
+
+    fn key(input: &mut Input, code: KeyCode, logical: &str, pressed: bool) {
+        input.handle_key(
+            PhysicalKey::Code(code),
+            Key::Character(SmolStr::new(logical)),
+            pressed,
+            false,
+        );
+    }
+
+    fn touch(id: u64, phase: TouchPhase, x: f64, y: f64) -> WindowEvent {
+        WindowEvent::Touch(Touch {
+            device_id: DeviceId::dummy(),
+            phase,
+            location: PhysicalPosition::new(x, y),
+            force: None,
+            id,
+        })
+    }
+
+    fn mouse(button: MouseButton, state: ElementState) -> WindowEvent {
+        WindowEvent::MouseInput {
+            device_id: DeviceId::dummy(),
+            state,
+            button,
+        }
+    }
+
+    #[test]
+    fn keys_are_pressed_until_released() {
+        let mut input = Input::default();
+        key(&mut input, KeyCode::KeyA, "a", true);
+        assert!(input.key_pressed(KeyCode::KeyA));
+        assert!(input.key_just_pressed(KeyCode::KeyA));
+
+        input.end_frame();
+        input.handle_key(
+            PhysicalKey::Code(KeyCode::KeyA),
+            Key::Character(SmolStr::new("a")),
+            true,
+            true,
+        );
+        assert!(input.key_pressed(KeyCode::KeyA));
+        assert!(!input.key_just_pressed(KeyCode::KeyA));
+
+        key(&mut input, KeyCode::KeyA, "a", false);
+        assert!(!input.key_pressed(KeyCode::KeyA));
+        assert!(input.key_just_released(KeyCode::KeyA));
+        input.end_frame();
+        assert!(!input.key_just_released(KeyCode::KeyA));
+    }
+
+    #[test]
+    fn logical_key_is_released_as_pressed() {
+        let mut input = Input::default();
+        let (lower, upper) = (Key::Character("a".into()), Key::Character("A".into()));
+
+        // shift let go in between, so the release reports the lowercase key
+        key(&mut input, KeyCode::KeyA, "A", true);
+        key(&mut input, KeyCode::KeyA, "a", false);
+        assert!(!input.logical_key_pressed(&upper));
+        assert!(input.logical_key_just_released(&upper));
+        assert!(!input.logical_key_just_released(&lower));
+
+        input.handle_key(
+            PhysicalKey::Code(KeyCode::Escape),
+            Key::Named(NamedKey::Escape),
+            true,
+            false,
+        );
+        assert!(input.logical_key_just_pressed(&Key::Named(NamedKey::Escape)));
+    }
+
+    #[test]
+    fn focus_loss_releases_everything() {
+        let mut input = Input::default();
+        key(&mut input, KeyCode::KeyA, "a", true);
+        input.handle_window_event(&mouse(MouseButton::Left, ElementState::Pressed));
+        input.handle_window_event(&touch(1, TouchPhase::Started, 1.0, 2.0));
+        input.end_frame();
+
+        input.handle_window_event(&WindowEvent::Focused(false));
+        assert!(!input.key_pressed(KeyCode::KeyA));
+        assert!(input.key_just_released(KeyCode::KeyA));
+        assert!(input.logical_key_just_released(&Key::Character("a".into())));
+        assert!(input.mouse_just_released(MouseButton::Left));
+        assert_eq!(input.touches().count(), 0);
+        assert_eq!(input.touches_just_ended()[0].id, 1);
+
+        // the release arriving after focus returns changes nothing
+        input.end_frame();
+        key(&mut input, KeyCode::KeyA, "a", false);
+        assert!(!input.key_just_released(KeyCode::KeyA));
+    }
+
+    #[test]
+    fn short_touches_are_reported_as_started_and_ended() {
+        let mut input = Input::default();
+        input.handle_window_event(&touch(1, TouchPhase::Started, 1.0, 2.0));
+        input.handle_window_event(&touch(2, TouchPhase::Started, 5.0, 5.0));
+        input.handle_window_event(&touch(2, TouchPhase::Moved, 6.0, 7.0));
+        input.handle_window_event(&touch(1, TouchPhase::Ended, 3.0, 4.0));
+
+        let mut started: Vec<TouchPoint> = input.touches_just_started().copied().collect();
+        started.sort_by_key(|point| point.id);
+        assert_eq!(started[0].id, 1);
+        assert_eq!(started[0].position, PhysicalPosition::new(1.0, 2.0));
+        assert_eq!(started[1].position, PhysicalPosition::new(6.0, 7.0));
+        assert_eq!(started[1].start_position, PhysicalPosition::new(5.0, 5.0));
+
+        let ended = input.touches_just_ended();
+        assert_eq!(ended.len(), 1);
+        assert_eq!(ended[0].position, PhysicalPosition::new(3.0, 4.0));
+        assert_eq!(input.touches().count(), 1);
+
+        input.end_frame();
+        assert_eq!(input.touches_just_started().count(), 0);
+        assert!(input.touches_just_ended().is_empty());
+    }
+
+    #[test]
+    fn motion_accumulates_per_frame() {
+        let mut input = Input::default();
+        let cursor = |x, y| WindowEvent::CursorMoved {
+            device_id: DeviceId::dummy(),
+            position: PhysicalPosition::new(x, y),
+        };
+        input.handle_window_event(&cursor(10.0, 10.0));
+        input.handle_window_event(&cursor(12.0, 13.0));
+        input.handle_window_event(&cursor(15.0, 14.0));
+        assert_eq!(input.cursor_delta(), (5.0, 4.0));
+
+        input.handle_window_event(&WindowEvent::MouseWheel {
+            device_id: DeviceId::dummy(),
+            delta: MouseScrollDelta::LineDelta(0.0, 1.0),
+            phase: TouchPhase::Moved,
+        });
+        input.handle_window_event(&WindowEvent::MouseWheel {
+            device_id: DeviceId::dummy(),
+            delta: MouseScrollDelta::PixelDelta(PhysicalPosition::new(40.0, 10.0)),
+            phase: TouchPhase::Moved,
+        });
+        assert_eq!(input.scroll_delta(), (2.0, 1.5));
+
+        input.handle_window_event(&WindowEvent::CursorLeft {
+            device_id: DeviceId::dummy(),
+        });
+        assert_eq!(input.cursor_position(), None);
+
+        input.end_frame();
+        assert_eq!(input.cursor_delta(), (0.0, 0.0));
+        assert_eq!(input.scroll_delta(), (0.0, 0.0));
+    }
+}
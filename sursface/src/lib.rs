@@ -1,7 +1,21 @@
 pub use {image, log, web_time, wgpu, winit};
 
+// Lets tests include generated code, which names the crate `sursface`.
+#[cfg(test)]
+extern crate self as sursface;
+
+#[cfg(feature = "egui")]
+pub use egui;
+
 pub mod app;
+#[cfg(feature = "camera")]
+pub mod camera;
 pub mod display;
+pub mod gpu;
+pub mod input;
+#[cfg(feature = "math")]
+pub mod math;
+pub mod overlay;
 pub mod start;
 pub mod std;
 pub mod time;
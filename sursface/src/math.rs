@@ -0,0 +1,263 @@
+pub use glam;
+pub use glam::{Mat3, Mat4, Quat, Vec2, Vec3, Vec4};
+
+/// Right-handed perspective projection into wgpu clip space (depth 0..1). `fovy` is in radians.
+pub fn perspective(fovy: f32, aspect: f32, near: f32, far: f32) -> Mat4 {
+    Mat4::perspective_rh(fovy, aspect, near, far)
+}
+
+/// Right-handed orthographic projection into wgpu clip space (depth 0..1).
+pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Mat4 {
+    Mat4::orthographic_rh(left, right, bottom, top, near, far)
+}
+
+pub fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Mat4 {
+    Mat4::look_at_rh(eye, target, up)
+}
+
+/// Translation, rotation and non-uniform scale, applied in that order from the outside in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn from_rotation(rotation: Quat) -> Self {
+        Self {
+            rotation,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn from_scale(scale: Vec3) -> Self {
+        Self {
+            scale,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Placed at `eye` with -Z pointing at `target`, like a camera.
+    pub fn looking_at(eye: Vec3, target: Vec3, up: Vec3) -> Self {
+        Self::from_matrix(look_at(eye, target, up).inverse())
+    }
+
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn with_translation(mut self, translation: Vec3) -> Self {
+        self.translation = translation;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: Quat) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Vec3) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+
+    /// The matrix for transforming normals, which stays correct under non-uniform scale.
+    pub fn normal_matrix(&self) -> Mat3 {
+        Mat3::from_mat4(self.matrix()).inverse().transpose()
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        self.rotation * Vec3::NEG_Z
+    }
+
+    pub fn right(&self) -> Vec3 {
+        self.rotation * Vec3::X
+    }
+
+    pub fn up(&self) -> Vec3 {
+        self.rotation * Vec3::Y
+    }
+
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        self.translation + self.rotation * (self.scale * point)
+    }
+
+    /// Applies `child` first, then `self`.
+    ///
+    /// Exact only if `self` has uniform scale, or `child` isn't rotated. Otherwise the combined
+    /// transform has shear, which a [`Transform`] can't hold, and the scale is applied along
+    /// the child's axes instead; multiply the [`Transform::matrix`]es for the exact result.
+    pub fn mul_transform(&self, child: &Transform) -> Transform {
+        Self {
+            translation: self.transform_point(child.translation),
+            rotation: self.rotation * child.rotation,
+            scale: self.scale * child.scale,
+        }
+    }
+}
+
+impl From<Transform> for Mat4 {
+    fn from(transform: Transform) -> Self {
+        transform.matrix()
+    }
+}
+
+/// Conversion into the plain arrays used in `#[repr(C)]` uniform structs. Matrices are
+/// column-major, and `Mat3` columns are padded to 16 bytes as WGSL's `mat3x3` expects.
+pub trait ToUniform {
+    type Output: bytemuck::Pod;
+
+    fn to_uniform(&self) -> Self::Output;
+}
+
+impl ToUniform for Vec2 {
+    type Output = [f32; 2];
+
+    fn to_uniform(&self) -> Self::Output {
+        self.to_array()
+    }
+}
+
+impl ToUniform for Vec3 {
+    type Output = [f32; 3];
+
+    fn to_uniform(&self) -> Self::Output {
+        self.to_array()
+    }
+}
+
+impl ToUniform for Vec4 {
+    type Output = [f32; 4];
+
+    fn to_uniform(&self) -> Self::Output {
+        self.to_array()
+    }
+}
+
+impl ToUniform for Quat {
+    type Output = [f32; 4];
+
+    fn to_uniform(&self) -> Self::Output {
+        self.to_array()
+    }
+}
+
+impl ToUniform for Mat3 {
+    type Output = [[f32; 4]; 3];
+
+    fn to_uniform(&self) -> Self::Output {
+        [
+            self.x_axis.extend(0.0).to_array(),
+            self.y_axis.extend(0.0).to_array(),
+            self.z_axis.extend(0.0).to_array(),
+        ]
+    }
+}
+
+impl ToUniform for Mat4 {
+    type Output = [[f32; 4]; 4];
+
+    fn to_uniform(&self) -> Self::Output {
+        self.to_cols_array_2d()
+    }
+}
+
+impl ToUniform for Transform {
+    type Output = [[f32; 4]; 4];
+
+    fn to_uniform(&self) -> Self::Output {
+        self.matrix().to_uniform()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn depth(projection: Mat4, z: f32) -> f32 {
+        projection.project_point3(Vec3::new(0.0, 0.0, z)).z
+    }
+
+    #[test]
+    fn projections_map_near_and_far_to_depth_0_1() {
+        let perspective = perspective(1.0, 1.5, 0.1, 100.0);
+        assert!(depth(perspective, -0.1).abs() < 1e-5);
+        assert!((depth(perspective, -100.0) - 1.0).abs() < 1e-5);
+        assert!(depth(perspective, -10.0) > 0.0 && depth(perspective, -10.0) < 1.0);
+
+        let orthographic = orthographic(-2.0, 2.0, -1.0, 1.0, 1.0, 11.0);
+        assert!(depth(orthographic, -1.0).abs() < 1e-6);
+        assert!((depth(orthographic, -11.0) - 1.0).abs() < 1e-6);
+        assert!((depth(orthographic, -6.0) - 0.5).abs() < 1e-6);
+        let corner = orthographic.project_point3(Vec3::new(2.0, 1.0, -1.0));
+        assert!(corner.abs_diff_eq(Vec3::new(1.0, 1.0, 0.0), 1e-6));
+    }
+
+    #[test]
+    fn look_at_looks_down_negative_z() {
+        let (eye, target) = (Vec3::new(1.0, 2.0, 3.0), Vec3::new(-4.0, 0.0, 1.0));
+        let view = look_at(eye, target, Vec3::Y);
+        let distance = eye.distance(target);
+        assert!(view
+            .transform_point3(target)
+            .abs_diff_eq(Vec3::new(0.0, 0.0, -distance), 1e-5));
+        assert!(view.transform_point3(eye).abs_diff_eq(Vec3::ZERO, 1e-5));
+
+        let camera = Transform::looking_at(eye, target, Vec3::Y);
+        assert!(camera.translation.abs_diff_eq(eye, 1e-5));
+        assert!(camera
+            .forward()
+            .abs_diff_eq((target - eye).normalize(), 1e-5));
+        assert!(camera.up().y > 0.0);
+    }
+
+    #[test]
+    fn mul_transform_matches_the_matrix_product_for_uniform_scale() {
+        let parent = Transform::from_translation(Vec3::new(1.0, 2.0, 3.0))
+            .with_rotation(Quat::from_rotation_y(0.7))
+            .with_scale(Vec3::splat(2.0));
+        let child = Transform::from_translation(Vec3::new(-1.0, 0.5, 0.0))
+            .with_rotation(Quat::from_rotation_x(1.2))
+            .with_scale(Vec3::new(1.0, 3.0, 0.5));
+
+        let combined = parent.mul_transform(&child);
+        assert!(combined
+            .matrix()
+            .abs_diff_eq(parent.matrix() * child.matrix(), 1e-5));
+
+        let point = Vec3::new(0.3, -2.0, 4.0);
+        assert!(combined
+            .transform_point(point)
+            .abs_diff_eq(parent.transform_point(child.transform_point(point)), 1e-5));
+    }
+}
@@ -0,0 +1,144 @@
+use std::any::Any;
+
+use egui_wgpu::{Renderer, ScreenDescriptor};
+use wgpu::CommandEncoder;
+use winit::event::WindowEvent;
+use winit::window::Window;
+
+use crate::display::Display;
+use crate::std::target::{load_target, RenderTarget, TargetFormat};
+
+use super::Overlay;
+
+/// An [`Overlay`] running egui. Build the UI with [`EguiOverlay::context`] during
+/// [`crate::app::AppState::draw`], then call [`Display::draw_overlay`] before presenting.
+pub struct EguiOverlay {
+    context: egui::Context,
+    state: egui_winit::State,
+    /// Created on first render; later renders must use a target of the same format.
+    renderer: Option<(TargetFormat, Renderer)>,
+    /// Texture changes from frames that weren't rendered, applied on the next render.
+    textures: egui::TexturesDelta,
+    in_frame: bool,
+}
+
+impl EguiOverlay {
+    pub fn new(display: &Display) -> Self {
+        let context = egui::Context::default();
+        let state = egui_winit::State::new(
+            context.clone(),
+            egui::ViewportId::ROOT,
+            display.window(),
+            Some(display.window.scale_factor() as f32),
+            display.window.theme(),
+            Some(display.device.limits().max_texture_dimension_2d as usize),
+        );
+
+        Self {
+            context,
+            state,
+            renderer: None,
+            textures: egui::TexturesDelta::default(),
+            in_frame: false,
+        }
+    }
+
+    pub fn context(&self) -> &egui::Context {
+        &self.context
+    }
+
+    fn finish_frame(&mut self, window: &Window) -> egui::FullOutput {
+        self.in_frame = false;
+        let mut output = self.context.end_pass();
+        self.state
+            .handle_platform_output(window, std::mem::take(&mut output.platform_output));
+        self.textures
+            .append(std::mem::take(&mut output.textures_delta));
+        output
+    }
+}
+
+impl Overlay for EguiOverlay {
+    fn window_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        self.state.on_window_event(window, event).consumed
+    }
+
+    fn begin_frame(&mut self, window: &Window) {
+        if self.in_frame {
+            self.finish_frame(window);
+        }
+        let input = self.state.take_egui_input(window);
+        self.context.begin_pass(input);
+        self.in_frame = true;
+    }
+
+    fn render(&mut self, display: &Display, encoder: &mut CommandEncoder, target: &RenderTarget) {
+        if !self.in_frame {
+            return;
+        }
+        let output = self.finish_frame(display.window());
+
+        let format = target.target_format();
+        let (renderer_format, renderer) = self.renderer.get_or_insert_with(|| {
+            let renderer = Renderer::new(
+                &display.device,
+                format.format,
+                format.depth_format,
+                format.sample_count,
+                false,
+            );
+            (format, renderer)
+        });
+        if *renderer_format != format {
+            log::warn!(
+                "egui overlay was created for {:?} but drawn into {:?}",
+                renderer_format,
+                format
+            );
+            return;
+        }
+
+        let screen = ScreenDescriptor {
+            size_in_pixels: [target.width, target.height],
+            pixels_per_point: output.pixels_per_point,
+        };
+        let primitives = self
+            .context
+            .tessellate(output.shapes, output.pixels_per_point);
+
+        let textures = std::mem::take(&mut self.textures);
+        for (id, delta) in &textures.set {
+            renderer.update_texture(&display.device, &display.queue, *id, delta);
+        }
+        let callbacks = renderer.update_buffers(
+            &display.device,
+            &display.queue,
+            encoder,
+            &primitives,
+            &screen,
+        );
+        if !callbacks.is_empty() {
+            display.queue.submit(callbacks);
+        }
+
+        {
+            let mut rpass = load_target(target, encoder).forget_lifetime();
+            renderer.render(&mut rpass, &primitives, &screen);
+        }
+
+        for id in &textures.free {
+            renderer.free_texture(id);
+        }
+    }
+
+    fn end_frame(&mut self, window: &Window) {
+        if self.in_frame {
+            // Nothing was drawn this frame, but egui still expects the pass to end.
+            self.finish_frame(window);
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -0,0 +1,45 @@
+use std::any::Any;
+
+use wgpu::CommandEncoder;
+use winit::event::{ElementState, TouchPhase, WindowEvent};
+use winit::window::Window;
+
+use crate::display::Display;
+use crate::std::target::RenderTarget;
+
+#[cfg(feature = "egui")]
+pub mod egui;
+
+/// A UI layer that sees window events before the [`crate::app::AppState`] and draws on top of
+/// its output. Install one through [`crate::app::AppState::create_overlay`].
+pub trait Overlay: Any {
+    /// Returns `true` if the overlay captured the event, in which case the app gets it through
+    /// [`crate::app::AppState::captured_event`] instead of `event`.
+    fn window_event(&mut self, window: &Window, event: &WindowEvent) -> bool;
+
+    /// Called before [`crate::app::AppState::draw`].
+    fn begin_frame(&mut self, window: &Window);
+
+    /// Draws the overlay over the existing contents of `target`. Called through
+    /// [`Display::draw_overlay`].
+    fn render(&mut self, display: &Display, encoder: &mut CommandEncoder, target: &RenderTarget);
+
+    /// Called after [`crate::app::AppState::draw`], whether or not the overlay was rendered.
+    fn end_frame(&mut self, window: &Window) {
+        let _ = window;
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// Whether [`crate::input::Input`] should still see an event the overlay captured. Releases
+/// always get through so nothing stays held.
+pub(crate) fn reaches_input(event: &WindowEvent) -> bool {
+    match event {
+        WindowEvent::KeyboardInput { event, .. } => event.state == ElementState::Released,
+        WindowEvent::MouseInput { state, .. } => *state == ElementState::Released,
+        WindowEvent::Touch(touch) => touch.phase != TouchPhase::Started,
+        WindowEvent::MouseWheel { .. } | WindowEvent::Ime(_) => false,
+        _ => true,
+    }
+}
@@ -1,4 +1,4 @@
-use winit::event_loop::{ControlFlow, EventLoop};
+use winit::event_loop::EventLoop;
 
 #[cfg(target_arch = "wasm32")]
 use wgpu::web_sys::HtmlCanvasElement;
@@ -12,18 +12,17 @@ extern crate console_error_panic_hook;
 pub fn create_window_desktop<State: AppState + 'static>(
     width: u32, height: u32,
 ) {
-    let event_loop = EventLoop::new().unwrap();
-    event_loop.set_control_flow(ControlFlow::Poll);
+    let event_loop = EventLoop::with_user_event().build().unwrap();
 
-    let mut app = App::<State>::from_window_size(width, height);
+    let mut app =
+        App::<State>::from_window_size(width, height).with_proxy(event_loop.create_proxy());
     event_loop.run_app(&mut app).unwrap();
 }
 
 #[cfg(target_arch = "wasm32")]
 pub fn create_window_browser<State: AppState + 'static>(canvas: HtmlCanvasElement) {
-    let event_loop = EventLoop::new().unwrap();
-    event_loop.set_control_flow(ControlFlow::Poll);
+    let event_loop = EventLoop::with_user_event().build().unwrap();
 
-    let mut app = App::<State>::from_canvas(canvas);
+    let mut app = App::<State>::from_canvas(canvas).with_proxy(event_loop.create_proxy());
     event_loop.run_app(&mut app).unwrap();
 }
@@ -0,0 +1,785 @@
+use std::any::Any;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+
+use wgpu::util::DeviceExt;
+use wgpu::{Buffer, Device, Queue, TextureView};
+
+use super::models::VertexPositionNormalUv;
+use super::shader::ShaderError;
+use super::source::AssetSource;
+
+#[derive(Clone, Debug)]
+pub struct AssetError {
+    pub path: String,
+    pub message: String,
+    pub kind: AssetErrorKind,
+}
+
+#[derive(Clone, Debug)]
+pub enum AssetErrorKind {
+    /// The file couldn't be read or decoded.
+    Other,
+    /// The file is a shader that failed WGSL validation.
+    Shader(Box<ShaderError>),
+}
+
+impl AssetError {
+    pub fn new(path: &str, message: impl fmt::Display) -> Self {
+        Self {
+            path: path.to_owned(),
+            message: message.to_string(),
+            kind: AssetErrorKind::Other,
+        }
+    }
+
+    pub fn shader(path: &str, error: ShaderError) -> Self {
+        Self {
+            kind: AssetErrorKind::Shader(Box::new(error.clone())),
+            ..Self::new(path, error)
+        }
+    }
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to load {}: {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for AssetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            AssetErrorKind::Other => None,
+            AssetErrorKind::Shader(error) => Some(error.as_ref()),
+        }
+    }
+}
+
+pub type FetchCallback = Box<dyn FnOnce(Result<Vec<u8>, AssetError>) + Send>;
+
+/// Where an [`AssetManager`] gets file contents from. `fetch` may call `done` right away or
+/// later from any thread, e.g. once a browser `fetch` resolves.
+pub trait AssetFetcher: Send + Sync + 'static {
+    fn fetch(&self, path: &str, done: FetchCallback);
+}
+
+/// Reads files relative to `root`. Runs on the asset manager's worker threads.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct FileFetcher {
+    pub root: std::path::PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileFetcher {
+    pub fn new(root: impl Into<std::path::PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl AssetFetcher for FileFetcher {
+    fn fetch(&self, path: &str, done: FetchCallback) {
+        done(std::fs::read(self.root.join(path)).map_err(|e| AssetError::new(path, e)));
+    }
+}
+
+/// Fetches files relative to `base_url` with the browser's `fetch`, so loading doesn't block the
+/// page. Decoding still runs on the main thread once the download finishes.
+#[cfg(target_arch = "wasm32")]
+pub struct WebFetcher {
+    pub base_url: String,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl WebFetcher {
+    /// `base_url` is put in front of every path as is, e.g. `"assets/"`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl AssetFetcher for WebFetcher {
+    fn fetch(&self, path: &str, done: FetchCallback) {
+        let url = format!("{}{}", self.base_url, path);
+        let path = path.to_owned();
+        wasm_bindgen_futures::spawn_local(async move {
+            done(
+                fetch_bytes(&url)
+                    .await
+                    .map_err(|e| AssetError::new(&path, e)),
+            );
+        });
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn fetch_bytes(url: &str) -> Result<Vec<u8>, String> {
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+
+    let describe = |e: wasm_bindgen::JsValue| format!("{:?}", e);
+    let window = web_sys::window().ok_or("no window")?;
+    let response: web_sys::Response = JsFuture::from(window.fetch_with_str(url))
+        .await
+        .map_err(describe)?
+        .dyn_into()
+        .map_err(describe)?;
+    if !response.ok() {
+        return Err(format!("HTTP {}", response.status()));
+    }
+    let buffer = JsFuture::from(response.array_buffer().map_err(describe)?)
+        .await
+        .map_err(describe)?;
+    Ok(web_sys::js_sys::Uint8Array::new(&buffer).to_vec())
+}
+
+/// Fetches from an [`AssetSource`], on the asset manager's worker threads.
+pub struct SourceFetcher<S>(pub S);
+
+impl<S: AssetSource + 'static> AssetFetcher for SourceFetcher<S> {
+    fn fetch(&self, path: &str, done: FetchCallback) {
+        done(self.0.read(path).map(Cow::into_owned));
+    }
+}
+
+/// Something an [`AssetManager`] can load. Decoding runs off the main thread; uploading runs
+/// in [`AssetManager::update`].
+pub trait Asset: Sized + 'static {
+    type Decoded: Send + 'static;
+
+    fn decode(path: &str, bytes: Vec<u8>) -> Result<Self::Decoded, AssetError>;
+    fn upload(decoded: Self::Decoded, device: &Device, queue: &Queue) -> Self;
+}
+
+impl Asset for Vec<u8> {
+    type Decoded = Vec<u8>;
+
+    fn decode(_path: &str, bytes: Vec<u8>) -> Result<Self::Decoded, AssetError> {
+        Ok(bytes)
+    }
+
+    fn upload(decoded: Self::Decoded, _device: &Device, _queue: &Queue) -> Self {
+        decoded
+    }
+}
+
+/// Text such as shader source.
+impl Asset for String {
+    type Decoded = String;
+
+    fn decode(path: &str, bytes: Vec<u8>) -> Result<Self::Decoded, AssetError> {
+        String::from_utf8(bytes).map_err(|e| AssetError::new(path, e))
+    }
+
+    fn upload(decoded: Self::Decoded, _device: &Device, _queue: &Queue) -> Self {
+        decoded
+    }
+}
+
+/// An image uploaded as an `Rgba8UnormSrgb` texture.
+pub struct TextureAsset {
+    pub texture: wgpu::Texture,
+    pub view: TextureView,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Asset for TextureAsset {
+    type Decoded = image::RgbaImage;
+
+    fn decode(path: &str, bytes: Vec<u8>) -> Result<Self::Decoded, AssetError> {
+        image::load_from_memory(&bytes)
+            .map(|image| image.to_rgba8())
+            .map_err(|e| AssetError::new(path, e))
+    }
+
+    fn upload(decoded: Self::Decoded, device: &Device, queue: &Queue) -> Self {
+        let texture = super::atlas::upload_rgba(device, queue, &decoded);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            texture,
+            view,
+            width: decoded.width(),
+            height: decoded.height(),
+        }
+    }
+}
+
+/// A Wavefront OBJ model flattened into a triangle list.
+pub struct MeshAsset {
+    pub vertices: Vec<VertexPositionNormalUv>,
+    pub vertex_buffer: Buffer,
+}
+
+impl MeshAsset {
+    pub fn vertex_count(&self) -> u32 {
+        self.vertices.len() as u32
+    }
+}
+
+impl Asset for MeshAsset {
+    type Decoded = Vec<VertexPositionNormalUv>;
+
+    fn decode(path: &str, bytes: Vec<u8>) -> Result<Self::Decoded, AssetError> {
+        let source = String::from_utf8(bytes).map_err(|e| AssetError::new(path, e))?;
+        parse_obj(&source).map_err(|e| AssetError::new(path, e))
+    }
+
+    fn upload(decoded: Self::Decoded, device: &Device, _queue: &Queue) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh Vertex Buffer"),
+            contents: bytemuck::cast_slice(&decoded),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        Self {
+            vertices: decoded,
+            vertex_buffer,
+        }
+    }
+}
+
+/// Reads and parses the OBJ file at `path`, see [`parse_obj`].
+pub fn load_mesh(
+    source: &dyn AssetSource,
+    path: &str,
+) -> Result<Vec<VertexPositionNormalUv>, AssetError> {
+    parse_obj(&source.read_to_string(path)?).map_err(|e| AssetError::new(path, e))
+}
+
+/// Parses the positions, texture coordinates, normals and faces of an OBJ file. Polygons are
+/// triangulated as fans; faces without normals get a flat one.
+pub fn parse_obj(source: &str) -> Result<Vec<VertexPositionNormalUv>, String> {
+    fn floats<const N: usize>(parts: &[&str], line: usize) -> Result<[f32; N], String> {
+        let mut values = [0.0; N];
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = match parts.get(i) {
+                Some(part) => part
+                    .parse()
+                    .map_err(|_| format!("line {}: invalid number '{}'", line, part))?,
+                None => return Err(format!("line {}: expected {} numbers", line, N)),
+            };
+        }
+        Ok(values)
+    }
+
+    fn index(part: Option<&str>, len: usize, line: usize) -> Result<Option<usize>, String> {
+        let Some(part) = part.filter(|part| !part.is_empty()) else {
+            return Ok(None);
+        };
+        let index: i64 = part
+            .parse()
+            .map_err(|_| format!("line {}: invalid index '{}'", line, part))?;
+        // OBJ indices are 1-based; negative ones count back from the latest element.
+        let resolved = if index < 0 {
+            len as i64 + index
+        } else {
+            index - 1
+        };
+        if resolved < 0 || resolved as usize >= len {
+            return Err(format!("line {}: index {} out of range", line, index));
+        }
+        Ok(Some(resolved as usize))
+    }
+
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+    let mut vertices = Vec::new();
+
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let mut parts = line.split_whitespace();
+        let Some(keyword) = parts.next() else {
+            continue;
+        };
+        let parts: Vec<&str> = parts.collect();
+
+        match keyword {
+            "v" => positions.push(floats(&parts, line_number)?),
+            "vt" => {
+                let [u, v] = floats(&parts, line_number)?;
+                // OBJ puts v = 0 at the bottom, textures have it at the top.
+                uvs.push([u, 1.0 - v]);
+            }
+            "vn" => normals.push(floats(&parts, line_number)?),
+            "f" => {
+                if parts.len() < 3 {
+                    return Err(format!("line {}: face needs 3 vertices", line_number));
+                }
+
+                let mut corners = Vec::with_capacity(parts.len());
+                for part in &parts {
+                    let mut indices = part.split('/');
+                    let position = index(indices.next(), positions.len(), line_number)?
+                        .ok_or_else(|| format!("line {}: face without position", line_number))?;
+                    let uv = index(indices.next(), uvs.len(), line_number)?;
+                    let normal = index(indices.next(), normals.len(), line_number)?;
+                    corners.push((position, uv, normal));
+                }
+
+                for k in 1..corners.len() - 1 {
+                    let triangle = [corners[0], corners[k], corners[k + 1]];
+                    let flat = flat_normal(triangle.map(|(p, _, _)| positions[p]));
+                    for (position, uv, normal) in triangle {
+                        vertices.push(VertexPositionNormalUv::new(
+                            positions[position],
+                            normal.map_or(flat, |n| normals[n]),
+                            uv.map_or([0.0, 0.0], |t| uvs[t]),
+                        ));
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    Ok(vertices)
+}
+
+fn flat_normal([a, b, c]: [[f32; 3]; 3]) -> [f32; 3] {
+    let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    let n = [
+        u[1] * v[2] - u[2] * v[1],
+        u[2] * v[0] - u[0] * v[2],
+        u[0] * v[1] - u[1] * v[0],
+    ];
+    let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+    if length == 0.0 {
+        [0.0, 0.0, 0.0]
+    } else {
+        [n[0] / length, n[1] / length, n[2] / length]
+    }
+}
+
+/// Refers to an asset that may still be loading.
+pub struct Handle<T> {
+    id: u64,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Handle").field(&self.id).finish()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum LoadState {
+    Loading,
+    Loaded,
+    Failed(AssetError),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    pub loaded: usize,
+    pub failed: usize,
+    pub total: usize,
+}
+
+impl Progress {
+    /// Share of the assets that finished, successfully or not, from 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            (self.loaded + self.failed) as f32 / self.total as f32
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.loaded + self.failed == self.total
+    }
+}
+
+type Upload = Box<dyn FnOnce(&Device, &Queue) -> Box<dyn Any> + Send>;
+type Job = Box<dyn FnOnce() + Send>;
+type Finished = (u64, Result<Upload, AssetError>);
+type Waker = Arc<dyn Fn() + Send + Sync>;
+
+enum Slot {
+    Loading,
+    Loaded(Box<dyn Any>),
+    Failed(AssetError),
+}
+
+/// Runs jobs on a few worker threads. On wasm, where there are no threads, jobs run
+/// immediately.
+#[derive(Clone)]
+struct Workers {
+    #[cfg(not(target_arch = "wasm32"))]
+    jobs: Sender<Job>,
+}
+
+impl Workers {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn new(threads: usize) -> Self {
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(std::sync::Mutex::new(receiver));
+
+        for i in 0..threads.max(1) {
+            let receiver = Arc::clone(&receiver);
+            std::thread::Builder::new()
+                .name(format!("sursface-assets-{}", i))
+                .spawn(move || loop {
+                    let job = match receiver.lock().unwrap().recv() {
+                        Ok(job) => job,
+                        Err(_) => return,
+                    };
+                    job();
+                })
+                .expect("Couldn't spawn asset worker");
+        }
+
+        Self { jobs }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn new(_threads: usize) -> Self {
+        Self {}
+    }
+
+    fn run(&self, job: Job) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.jobs.send(job).expect("asset workers stopped");
+        #[cfg(target_arch = "wasm32")]
+        job();
+    }
+}
+
+/// Loads and decodes assets in the background. `load` returns a [`Handle`] right away; call
+/// [`AssetManager::update`] every frame to upload whatever finished, then [`AssetManager::get`]
+/// it once loaded.
+pub struct AssetManager {
+    fetcher: Arc<dyn AssetFetcher>,
+    workers: Workers,
+    sender: Sender<Finished>,
+    receiver: Receiver<Finished>,
+    slots: HashMap<u64, Slot>,
+    next_id: u64,
+    waker: Option<Waker>,
+}
+
+impl AssetManager {
+    pub fn new(fetcher: impl AssetFetcher) -> Self {
+        let threads = std::thread::available_parallelism()
+            .map_or(2, |n| n.get())
+            .min(4);
+        Self::with_threads(fetcher, threads)
+    }
+
+    pub fn from_source(source: impl AssetSource + 'static) -> Self {
+        Self::new(SourceFetcher(source))
+    }
+
+    pub fn with_threads(fetcher: impl AssetFetcher, threads: usize) -> Self {
+        let (sender, receiver) = mpsc::channel();
+
+        Self {
+            fetcher: Arc::new(fetcher),
+            workers: Workers::new(threads),
+            sender,
+            receiver,
+            slots: HashMap::new(),
+            next_id: 0,
+            waker: None,
+        }
+    }
+
+    /// Called from the loading thread whenever an asset finishes, e.g. to send a user event
+    /// through the proxy passed to [`crate::app::AppState::new`] so a waiting event loop wakes
+    /// up.
+    pub fn set_waker(&mut self, waker: impl Fn() + Send + Sync + 'static) {
+        self.waker = Some(Arc::new(waker));
+    }
+
+    pub fn load<T: Asset>(&mut self, path: &str) -> Handle<T> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.slots.insert(id, Slot::Loading);
+
+        let fetcher = Arc::clone(&self.fetcher);
+        let workers = self.workers.clone();
+        let sender = self.sender.clone();
+        let waker = self.waker.clone();
+        let path = path.to_owned();
+
+        self.workers.run(Box::new(move || {
+            let fetch_path = path.clone();
+            let (fetch_sender, fetch_waker) = (sender.clone(), waker.clone());
+            let fetched = catch_panic(&fetch_path, || {
+                fetcher.fetch(
+                    &fetch_path,
+                    Box::new(move |bytes| {
+                        // The fetcher may call back on a thread of its own, so decode on ours.
+                        workers.run(Box::new(move || {
+                            let result = catch_panic(&path, || {
+                                bytes.and_then(|bytes| T::decode(&path, bytes))
+                            })
+                            .map(|decoded| -> Upload {
+                                Box::new(move |device, queue| {
+                                    Box::new(T::upload(decoded, device, queue))
+                                })
+                            });
+                            finish(&sender, waker.as_ref(), (id, result));
+                        }));
+                    }),
+                );
+                Ok(())
+            });
+            if let Err(e) = fetched {
+                finish(&fetch_sender, fetch_waker.as_ref(), (id, Err(e)));
+            }
+        }));
+
+        Handle {
+            id,
+            marker: PhantomData,
+        }
+    }
+
+    /// Uploads the assets that finished decoding since the last call.
+    pub fn update(&mut self, device: &Device, queue: &Queue) {
+        while let Ok((id, result)) = self.receiver.try_recv() {
+            // a fetcher that panicked after calling back reports twice; keep the first
+            if !matches!(self.slots.get(&id), Some(Slot::Loading)) {
+                continue;
+            }
+            let slot = match result {
+                Ok(upload) => Slot::Loaded(upload(device, queue)),
+                Err(e) => {
+                    log::error!("{}", e);
+                    Slot::Failed(e)
+                }
+            };
+            self.slots.insert(id, slot);
+        }
+    }
+
+    pub fn get<T: Asset>(&self, handle: Handle<T>) -> Option<&T> {
+        match self.slots.get(&handle.id)? {
+            Slot::Loaded(asset) => asset.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn state<T>(&self, handle: Handle<T>) -> LoadState {
+        match self.slots.get(&handle.id) {
+            Some(Slot::Loaded(_)) => LoadState::Loaded,
+            Some(Slot::Failed(e)) => LoadState::Failed(e.clone()),
+            Some(Slot::Loading) | None => LoadState::Loading,
+        }
+    }
+
+    pub fn progress(&self) -> Progress {
+        let mut progress = Progress {
+            total: self.slots.len(),
+            ..Default::default()
+        };
+        for slot in self.slots.values() {
+            match slot {
+                Slot::Loaded(_) => progress.loaded += 1,
+                Slot::Failed(_) => progress.failed += 1,
+                Slot::Loading => (),
+            }
+        }
+        progress
+    }
+}
+
+fn finish(sender: &Sender<Finished>, waker: Option<&Waker>, finished: Finished) {
+    // The manager may be gone, in which case nobody wants the result.
+    let _ = sender.send(finished);
+    if let Some(waker) = waker {
+        waker();
+    }
+}
+
+/// Turns a panic in `f` into an error for `path`, so one bad asset can't take a worker down.
+fn catch_panic<R>(path: &str, f: impl FnOnce() -> Result<R, AssetError>) -> Result<R, AssetError> {
+    std::panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown panic");
+        Err(AssetError::new(path, format!("panicked: {}", message)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::std::source::MemorySource;
+
+    const TRIANGLE: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+
+    #[test]
+    fn load_mesh_reads_from_source() {
+        let source = MemorySource::new()
+            .with("triangle.obj", TRIANGLE)
+            .with("broken.obj", "f 1 2 3\n");
+
+        let vertices = load_mesh(&source, "triangle.obj").unwrap();
+        assert_eq!(vertices.len(), 3);
+        let floats: &[f32] = bytemuck::cast_slice(&vertices);
+        assert_eq!(floats[8..11], [1.0, 0.0, 0.0]);
+
+        assert_eq!(
+            load_mesh(&source, "missing.obj").unwrap_err().path,
+            "missing.obj"
+        );
+        assert_eq!(
+            load_mesh(&source, "broken.obj").unwrap_err().path,
+            "broken.obj"
+        );
+    }
+
+    fn floats(vertices: &[VertexPositionNormalUv]) -> &[[f32; 8]] {
+        bytemuck::cast_slice(vertices)
+    }
+
+    #[test]
+    fn parse_obj_resolves_negative_indices() {
+        let source =
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 1\nvn 0 0 1\nf -3/-2/-1 -2/-1/-1 -1/-1/-1\n";
+        let vertices = parse_obj(source).unwrap();
+        assert_eq!(
+            floats(&vertices),
+            [
+                [0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0],
+                [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0],
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_obj_fills_in_missing_uvs_and_normals() {
+        // clockwise seen from +z, so the flat normal points down -z
+        let source = "v 0 0 0\nv 0 1 0\nv 1 0 0\nvn 1 0 0\nf 1 2 3\nf 1//1 2//1 3//1\n";
+        let vertices = parse_obj(source).unwrap();
+        let vertices = floats(&vertices);
+        assert_eq!(vertices.len(), 6);
+        for vertex in &vertices[..3] {
+            assert_eq!(vertex[3..], [0.0, 0.0, -1.0, 0.0, 0.0]);
+        }
+        for vertex in &vertices[3..] {
+            assert_eq!(vertex[3..], [1.0, 0.0, 0.0, 0.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn parse_obj_triangulates_polygons_as_fans() {
+        let source = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n";
+        let vertices = parse_obj(source).unwrap();
+        let positions: Vec<_> = floats(&vertices).iter().map(|v| [v[0], v[1]]).collect();
+        assert_eq!(
+            positions,
+            [
+                [0.0, 0.0],
+                [1.0, 0.0],
+                [1.0, 1.0],
+                [0.0, 0.0],
+                [1.0, 1.0],
+                [0.0, 1.0],
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_obj_reports_bad_lines() {
+        let error = |source: &str| parse_obj(source).unwrap_err();
+        assert_eq!(error("v 0 0 0\nf 1 2 3\n"), "line 2: index 2 out of range");
+        assert_eq!(
+            error("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 -4\n"),
+            "line 4: index -4 out of range"
+        );
+        assert_eq!(error("v 0 0 0\nf 0 1 1\n"), "line 2: index 0 out of range");
+        assert_eq!(error("v 0 0\n"), "line 1: expected 3 numbers");
+        assert_eq!(error("vt 0 x\n"), "line 1: invalid number 'x'");
+        assert_eq!(error("v 0 0 0\nf 1 1\n"), "line 2: face needs 3 vertices");
+        assert_eq!(
+            error("v 0 0 0\nf /1 1 1\n"),
+            "line 2: face without position"
+        );
+    }
+
+    struct Panics;
+
+    impl Asset for Panics {
+        type Decoded = ();
+
+        fn decode(_path: &str, _bytes: Vec<u8>) -> Result<Self::Decoded, AssetError> {
+            panic!("bad asset");
+        }
+
+        fn upload(_decoded: Self::Decoded, _device: &Device, _queue: &Queue) -> Self {
+            Panics
+        }
+    }
+
+    struct PanickingFetcher;
+
+    impl AssetFetcher for PanickingFetcher {
+        fn fetch(&self, _path: &str, _done: FetchCallback) {
+            panic!("no network");
+        }
+    }
+
+    fn wait(manager: &mut AssetManager) {
+        let gpu = crate::gpu::GpuContext::noop();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while !manager.progress().is_done() {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "assets never finished"
+            );
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            manager.update(&gpu.device, &gpu.queue);
+        }
+    }
+
+    #[test]
+    fn panics_fail_the_asset_and_keep_workers_alive() {
+        let source = MemorySource::new()
+            .with("triangle.obj", TRIANGLE)
+            .with("panics", "");
+        let mut manager = AssetManager::with_threads(SourceFetcher(source), 1);
+
+        let panics = manager.load::<Panics>("panics");
+        let mesh = manager.load::<MeshAsset>("triangle.obj");
+        wait(&mut manager);
+
+        match manager.state(panics) {
+            LoadState::Failed(e) => assert!(e.message.contains("bad asset"), "{}", e),
+            state => panic!("expected a failure, got {:?}", state),
+        }
+        assert_eq!(manager.get(mesh).unwrap().vertex_count(), 3);
+
+        let mut manager = AssetManager::with_threads(PanickingFetcher, 1);
+        let bytes = manager.load::<Vec<u8>>("anything");
+        wait(&mut manager);
+        assert!(matches!(manager.state(bytes), LoadState::Failed(_)));
+    }
+}
@@ -0,0 +1,351 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use image::RgbaImage;
+use wgpu::{Device, Queue, Texture, TextureView};
+
+use super::assets::AssetError;
+use super::models::quad_uvs;
+use super::source::AssetSource;
+
+/// Empty pixels kept around every image so linear filtering doesn't bleed neighbours in.
+const PADDING: u32 = 1;
+
+/// Where an image ended up in a [`TextureAtlas`], as UVs and in pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AtlasRegion {
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl AtlasRegion {
+    /// The region's corners in the order used by [`super::models::quad`].
+    pub fn quad_uvs(&self) -> [[f32; 2]; 4] {
+        quad_uvs(
+            (self.uv_min[0], self.uv_min[1]),
+            (self.uv_max[0], self.uv_max[1]),
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AtlasError {
+    /// Two images were added under the same name.
+    DuplicateName(String),
+    /// The images don't fit in the largest texture the device supports.
+    TooLarge { max_size: u32 },
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AtlasError::DuplicateName(name) => write!(f, "atlas image '{}' added twice", name),
+            AtlasError::TooLarge { max_size } => {
+                write!(f, "atlas images don't fit in a {0}x{0} texture", max_size)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AtlasError {}
+
+/// Atlas width and height, and where each image goes, in the order they were added.
+type Packing = (u32, u32, Vec<(u32, u32)>);
+
+/// Collects images and packs them into one texture at load time.
+#[derive(Default)]
+pub struct AtlasBuilder {
+    images: Vec<(String, RgbaImage)>,
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an encoded image (PNG, JPEG, ...) under `name`.
+    pub fn add(&mut self, name: &str, bytes: &[u8]) -> Result<&mut Self, image::ImageError> {
+        let image = image::load_from_memory(bytes)?.to_rgba8();
+        Ok(self.add_image(name, image))
+    }
+
+    /// Adds the encoded image at `path` in `source`, under its path.
+    pub fn load(&mut self, source: &dyn AssetSource, path: &str) -> Result<&mut Self, AssetError> {
+        let bytes = source.read(path)?;
+        let image = image::load_from_memory(&bytes).map_err(|e| AssetError::new(path, e))?;
+        Ok(self.add_image(path, image.to_rgba8()))
+    }
+
+    pub fn add_image(&mut self, name: &str, image: RgbaImage) -> &mut Self {
+        self.images.push((name.to_owned(), image));
+        self
+    }
+
+    /// Packs the images into rows ("shelves"), tallest first, then uploads the result.
+    pub fn build(&self, device: &Device, queue: &Queue) -> Result<TextureAtlas, AtlasError> {
+        let mut names = HashSet::with_capacity(self.images.len());
+        if let Some((name, _)) = self.images.iter().find(|(name, _)| !names.insert(name)) {
+            return Err(AtlasError::DuplicateName(name.clone()));
+        }
+
+        let max_size = device.limits().max_texture_dimension_2d;
+        let (width, height, placements) = self.pack(max_size)?;
+
+        let mut pixels = RgbaImage::new(width, height);
+        let mut regions = HashMap::with_capacity(self.images.len());
+
+        for ((name, image), (x, y)) in self.images.iter().zip(placements) {
+            image::imageops::replace(&mut pixels, image, x as i64, y as i64);
+            regions.insert(
+                name.clone(),
+                AtlasRegion {
+                    uv_min: [x as f32 / width as f32, y as f32 / height as f32],
+                    uv_max: [
+                        (x + image.width()) as f32 / width as f32,
+                        (y + image.height()) as f32 / height as f32,
+                    ],
+                    x,
+                    y,
+                    width: image.width(),
+                    height: image.height(),
+                },
+            );
+        }
+
+        let texture = upload_rgba(device, queue, &pixels);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Ok(TextureAtlas {
+            texture,
+            view,
+            width,
+            height,
+            regions,
+        })
+    }
+
+    fn pack(&self, max_size: u32) -> Result<Packing, AtlasError> {
+        let sizes: Vec<_> = self
+            .images
+            .iter()
+            .map(|(_, image)| image.dimensions())
+            .collect();
+        shelf_pack(&sizes, max_size)
+    }
+}
+
+/// Places images of the given sizes on shelves, tallest first, doubling the width until the
+/// shelves fit. Works in `u64` so oversized input reports [`AtlasError::TooLarge`] instead of
+/// overflowing.
+fn shelf_pack(sizes: &[(u32, u32)], max_size: u32) -> Result<Packing, AtlasError> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(sizes[i].1));
+
+    let padded = |i: usize| {
+        let (width, height) = sizes[i];
+        (
+            width as u64 + 2 * PADDING as u64,
+            height as u64 + 2 * PADDING as u64,
+        )
+    };
+    let area = order.iter().fold(0u64, |area, &i| {
+        let (w, h) = padded(i);
+        area.saturating_add(w.saturating_mul(h))
+    });
+    let widest = order.iter().map(|&i| padded(i).0).max().unwrap_or(1);
+
+    let max_size = max_size as u64;
+    let mut width = ((area as f64).sqrt().ceil() as u64)
+        .max(widest)
+        .next_power_of_two();
+
+    loop {
+        let mut placements = vec![(0, 0); sizes.len()];
+        let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+
+        for &i in &order {
+            let (w, h) = padded(i);
+            if x + w > width {
+                x = 0;
+                y += shelf_height;
+                shelf_height = 0;
+            }
+            placements[i] = (x + PADDING as u64, y + PADDING as u64);
+            x += w;
+            shelf_height = shelf_height.max(h);
+        }
+
+        let height = (y + shelf_height).max(1);
+        if height <= width || width >= max_size {
+            if width > max_size || height > max_size {
+                return Err(AtlasError::TooLarge {
+                    max_size: max_size as u32,
+                });
+            }
+            let placements = placements
+                .into_iter()
+                .map(|(x, y)| (x as u32, y as u32))
+                .collect();
+            return Ok((width as u32, height as u32, placements));
+        }
+        width *= 2;
+    }
+}
+
+pub struct TextureAtlas {
+    pub texture: Texture,
+    pub view: TextureView,
+    pub width: u32,
+    pub height: u32,
+    pub regions: HashMap<String, AtlasRegion>,
+}
+
+impl TextureAtlas {
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.regions.get(name)
+    }
+}
+
+pub(crate) fn upload_rgba(device: &Device, queue: &Queue, image: &RgbaImage) -> Texture {
+    let extent = wgpu::Extent3d {
+        width: image.width(),
+        height: image.height(),
+        depth_or_array_layers: 1,
+    };
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: extent,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+
+    queue.write_texture(
+        wgpu::TexelCopyTextureInfo {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        image,
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * image.width()),
+            rows_per_image: Some(image.height()),
+        },
+        extent,
+    );
+
+    texture
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::GpuContext;
+    use crate::std::source::MemorySource;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        RgbaImage::new(width, height)
+            .write_to(
+                &mut std::io::Cursor::new(&mut bytes),
+                image::ImageFormat::Png,
+            )
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn load_reads_from_source() {
+        let source = MemorySource::new()
+            .with("a.png", png(4, 2))
+            .with("b.png", png(3, 3))
+            .with("broken.png", b"not a png".to_vec());
+
+        let mut builder = AtlasBuilder::new();
+        builder.load(&source, "a.png").unwrap();
+        builder.load(&source, "b.png").unwrap();
+        assert_eq!(
+            builder.load(&source, "missing.png").err().unwrap().path,
+            "missing.png"
+        );
+        assert_eq!(
+            builder.load(&source, "broken.png").err().unwrap().path,
+            "broken.png"
+        );
+
+        let gpu = GpuContext::noop();
+        let atlas = builder.build(&gpu.device, &gpu.queue).unwrap();
+        assert_eq!(atlas.regions.len(), 2);
+        let a = atlas.region("a.png").unwrap();
+        assert_eq!((a.width, a.height), (4, 2));
+        let b = atlas.region("b.png").unwrap();
+        assert_eq!((b.width, b.height), (3, 3));
+    }
+
+    #[test]
+    fn build_rejects_duplicates_and_oversized_images() {
+        let gpu = GpuContext::noop();
+
+        let mut builder = AtlasBuilder::new();
+        builder
+            .add_image("a", RgbaImage::new(2, 2))
+            .add_image("b", RgbaImage::new(2, 2))
+            .add_image("a", RgbaImage::new(3, 3));
+        assert_eq!(
+            builder.build(&gpu.device, &gpu.queue).err(),
+            Some(AtlasError::DuplicateName("a".to_owned()))
+        );
+
+        let max_size = gpu.device.limits().max_texture_dimension_2d;
+        let mut builder = AtlasBuilder::new();
+        builder.add_image("wide", RgbaImage::new(max_size, 1));
+        assert_eq!(
+            builder.build(&gpu.device, &gpu.queue).err(),
+            Some(AtlasError::TooLarge { max_size })
+        );
+    }
+
+    #[test]
+    fn pack_keeps_images_apart() {
+        let mut builder = AtlasBuilder::new();
+        for (index, size) in [(0, 5), (1, 3), (2, 8), (3, 3), (4, 1)] {
+            builder.add_image(&index.to_string(), RgbaImage::new(size, size));
+        }
+        let (width, height, placements) = builder.pack(64).unwrap();
+
+        let rects: Vec<_> = placements
+            .iter()
+            .zip(&builder.images)
+            .map(|(&(x, y), (_, image))| (x, y, x + image.width(), y + image.height()))
+            .collect();
+        for (i, a) in rects.iter().enumerate() {
+            assert!(a.0 >= PADDING && a.1 >= PADDING && a.2 <= width && a.3 <= height);
+            for b in &rects[i + 1..] {
+                let apart = a.2 + PADDING <= b.0
+                    || b.2 + PADDING <= a.0
+                    || a.3 + PADDING <= b.1
+                    || b.3 + PADDING <= a.1;
+                assert!(apart, "{:?} and {:?} overlap", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn pack_reports_huge_input_without_overflowing() {
+        let too_large = Err(AtlasError::TooLarge { max_size: 8192 });
+        assert_eq!(shelf_pack(&[(70_000, 70_000)], 8192), too_large);
+        assert_eq!(shelf_pack(&[(u32::MAX, u32::MAX); 3], 8192), too_large);
+        assert_eq!(shelf_pack(&[(40_000, 40_000); 4], 8192), too_large);
+        assert_eq!(shelf_pack(&[], 8192), Ok((1, 1, Vec::new())));
+    }
+}
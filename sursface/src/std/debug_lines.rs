@@ -0,0 +1,535 @@
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, Buffer, Color, CommandEncoder, Device, Queue, RenderPass, RenderPipeline};
+
+use crate::display::Display;
+
+use super::create_labeled_shader;
+use super::target::{load_target, RenderTarget, TargetFormat};
+
+const SHADER: &str = r#"
+struct Globals {
+    view_projection: mat4x4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> globals: Globals;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, @location(1) color: vec4<f32>) -> VertexOutput {
+    var output: VertexOutput;
+    output.position = globals.view_projection * vec4<f32>(position, 1.0);
+    output.color = color;
+    return output;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    return input.color;
+}
+"#;
+
+const IDENTITY: [[f32; 4]; 4] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+/// Segments used for each circle of [`DebugLines::sphere_wire`].
+const CIRCLE_SEGMENTS: usize = 32;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+/// Immediate-mode 3D lines for gizmos: queue shapes every frame, then draw them all with one
+/// `LineList` draw call using the view-projection matrix set with
+/// [`DebugLines::set_view_projection`]. Matrices are column-major, as `Mat4::to_cols_array_2d`
+/// returns them.
+///
+/// With depth testing on and a depth attachment in the target, lines are hidden behind geometry.
+pub struct DebugLines {
+    pipeline: RenderPipeline,
+    globals: Buffer,
+    bind_group: BindGroup,
+    vertex_buffer: Buffer,
+    vertices: Vec<LineVertex>,
+    view_projection: [[f32; 4]; 4],
+    /// Vertices uploaded by the last `prepare`.
+    prepared: u32,
+}
+
+impl DebugLines {
+    pub fn new(device: &Device, target: &TargetFormat, depth_test: bool) -> Self {
+        let shader = create_labeled_shader(device, SHADER, "Debug Lines");
+
+        let globals = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Debug Lines Globals"),
+            contents: bytemuck::cast_slice(&[IDENTITY]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Debug Lines"),
+            entries: &[super::create_uniform_entry(0)],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Debug Lines"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: globals.as_entire_binding(),
+            }],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Debug Lines"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4],
+                }],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: target.depth_format.map(|format| wgpu::DepthStencilState {
+                format,
+                depth_write_enabled: false,
+                depth_compare: if depth_test {
+                    wgpu::CompareFunction::LessEqual
+                } else {
+                    wgpu::CompareFunction::Always
+                },
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: target.sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            globals,
+            bind_group,
+            vertex_buffer: create_vertex_buffer(device, 0),
+            vertices: Vec::new(),
+            view_projection: IDENTITY,
+            prepared: 0,
+        }
+    }
+
+    pub fn set_view_projection(&mut self, view_projection: [[f32; 4]; 4]) {
+        self.view_projection = view_projection;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    pub fn line(&mut self, from: [f32; 3], to: [f32; 3], color: Color) {
+        let color = [
+            color.r as f32,
+            color.g as f32,
+            color.b as f32,
+            color.a as f32,
+        ];
+        self.vertices.push(LineVertex {
+            position: from,
+            color,
+        });
+        self.vertices.push(LineVertex {
+            position: to,
+            color,
+        });
+    }
+
+    /// The twelve edges of an axis-aligned box.
+    pub fn aabb(&mut self, min: [f32; 3], max: [f32; 3], color: Color) {
+        let corner = |i: usize| {
+            [
+                if i & 1 == 0 { min[0] } else { max[0] },
+                if i & 2 == 0 { min[1] } else { max[1] },
+                if i & 4 == 0 { min[2] } else { max[2] },
+            ]
+        };
+        self.box_edges(std::array::from_fn(corner), color);
+    }
+
+    /// Three great circles, one around each axis.
+    pub fn sphere_wire(&mut self, center: [f32; 3], radius: f32, color: Color) {
+        for axis in 0..3 {
+            let point = |i: usize| {
+                let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+                let (sin, cos) = angle.sin_cos();
+                let mut point = center;
+                point[(axis + 1) % 3] += radius * cos;
+                point[(axis + 2) % 3] += radius * sin;
+                point
+            };
+
+            for i in 0..CIRCLE_SEGMENTS {
+                self.line(point(i), point(i + 1), color);
+            }
+        }
+    }
+
+    /// X, Y and Z axes in red, green and blue.
+    pub fn axes(&mut self, origin: [f32; 3], length: f32) {
+        let colors = [Color::RED, Color::GREEN, Color::BLUE];
+        for (axis, color) in colors.into_iter().enumerate() {
+            let mut end = origin;
+            end[axis] += length;
+            self.line(origin, end, color);
+        }
+    }
+
+    /// A square grid on the XZ plane with `divisions` cells along each side.
+    pub fn grid(&mut self, center: [f32; 3], size: f32, divisions: u32, color: Color) {
+        let half = size / 2.0;
+        let divisions = divisions.max(1);
+
+        for i in 0..=divisions {
+            let offset = -half + size * i as f32 / divisions as f32;
+            self.line(
+                [center[0] + offset, center[1], center[2] - half],
+                [center[0] + offset, center[1], center[2] + half],
+                color,
+            );
+            self.line(
+                [center[0] - half, center[1], center[2] + offset],
+                [center[0] + half, center[1], center[2] + offset],
+                color,
+            );
+        }
+    }
+
+    /// The volume seen through `view_projection`, e.g. another camera's, found by unprojecting
+    /// the corners of wgpu's clip space.
+    pub fn frustum(&mut self, view_projection: [[f32; 4]; 4], color: Color) {
+        let Some(inverse) = invert(view_projection) else {
+            return;
+        };
+
+        let corners = std::array::from_fn(|i| {
+            let clip = [
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { 0.0 } else { 1.0 },
+                1.0,
+            ];
+            let world: [f32; 4] = std::array::from_fn(|row| {
+                (0..4)
+                    .map(|column| inverse[column][row] * clip[column])
+                    .sum()
+            });
+            [
+                world[0] / world[3],
+                world[1] / world[3],
+                world[2] / world[3],
+            ]
+        });
+        self.box_edges(corners, color);
+    }
+
+    /// Connects eight corners indexed by their x, y and z bits.
+    fn box_edges(&mut self, corners: [[f32; 3]; 8], color: Color) {
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corners[i], corners[i | bit], color);
+                }
+            }
+        }
+    }
+
+    pub fn prepare(&mut self, device: &Device, queue: &Queue) {
+        queue.write_buffer(
+            &self.globals,
+            0,
+            bytemuck::cast_slice(&[self.view_projection]),
+        );
+
+        let vertices: &[u8] = bytemuck::cast_slice(&self.vertices);
+        if vertices.len() as u64 > self.vertex_buffer.size() {
+            self.vertex_buffer = create_vertex_buffer(device, vertices.len());
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, vertices);
+        self.prepared = self.vertices.len() as u32;
+    }
+
+    pub fn render(&self, rpass: &mut RenderPass) {
+        if self.prepared == 0 {
+            return;
+        }
+
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        rpass.draw(0..self.prepared, 0..1);
+    }
+
+    /// Draws the queued lines over `target` in their own pass and clears the queue.
+    pub fn flush(
+        &mut self,
+        display: &Display,
+        encoder: &mut CommandEncoder,
+        target: &RenderTarget,
+    ) {
+        if !self.is_empty() {
+            self.prepare(&display.device, &display.queue);
+            let mut rpass = load_target(target, encoder);
+            self.render(&mut rpass);
+        }
+        self.clear();
+    }
+}
+
+fn create_vertex_buffer(device: &Device, size: usize) -> Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Debug Lines"),
+        size: (size.max(1024).next_power_of_two()) as u64,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// Inverse of a column-major 4x4 matrix, `None` when singular.
+#[cfg(feature = "math")]
+fn invert(m: [[f32; 4]; 4]) -> Option<[[f32; 4]; 4]> {
+    let m = glam::Mat4::from_cols_array_2d(&m);
+    let det = m.determinant();
+    if det == 0.0 || !det.is_finite() {
+        return None;
+    }
+    Some(m.inverse().to_cols_array_2d())
+}
+
+#[cfg(not(feature = "math"))]
+fn invert(m: [[f32; 4]; 4]) -> Option<[[f32; 4]; 4]> {
+    invert_cofactor(m)
+}
+
+/// Inverse of a column-major 4x4 matrix by cofactor expansion, `None` when singular. Only used
+/// without the `math` feature, but always compiled so it's tested either way.
+#[cfg_attr(feature = "math", allow(dead_code))]
+fn invert_cofactor(m: [[f32; 4]; 4]) -> Option<[[f32; 4]; 4]> {
+    let a: [f32; 16] = std::array::from_fn(|i| m[i / 4][i % 4]);
+    let mut inv = [0.0f32; 16];
+
+    inv[0] = a[5] * a[10] * a[15] - a[5] * a[11] * a[14] - a[9] * a[6] * a[15]
+        + a[9] * a[7] * a[14]
+        + a[13] * a[6] * a[11]
+        - a[13] * a[7] * a[10];
+    inv[4] = -a[4] * a[10] * a[15] + a[4] * a[11] * a[14] + a[8] * a[6] * a[15]
+        - a[8] * a[7] * a[14]
+        - a[12] * a[6] * a[11]
+        + a[12] * a[7] * a[10];
+    inv[8] = a[4] * a[9] * a[15] - a[4] * a[11] * a[13] - a[8] * a[5] * a[15]
+        + a[8] * a[7] * a[13]
+        + a[12] * a[5] * a[11]
+        - a[12] * a[7] * a[9];
+    inv[12] = -a[4] * a[9] * a[14] + a[4] * a[10] * a[13] + a[8] * a[5] * a[14]
+        - a[8] * a[6] * a[13]
+        - a[12] * a[5] * a[10]
+        + a[12] * a[6] * a[9];
+    inv[1] = -a[1] * a[10] * a[15] + a[1] * a[11] * a[14] + a[9] * a[2] * a[15]
+        - a[9] * a[3] * a[14]
+        - a[13] * a[2] * a[11]
+        + a[13] * a[3] * a[10];
+    inv[5] = a[0] * a[10] * a[15] - a[0] * a[11] * a[14] - a[8] * a[2] * a[15]
+        + a[8] * a[3] * a[14]
+        + a[12] * a[2] * a[11]
+        - a[12] * a[3] * a[10];
+    inv[9] = -a[0] * a[9] * a[15] + a[0] * a[11] * a[13] + a[8] * a[1] * a[15]
+        - a[8] * a[3] * a[13]
+        - a[12] * a[1] * a[11]
+        + a[12] * a[3] * a[9];
+    inv[13] = a[0] * a[9] * a[14] - a[0] * a[10] * a[13] - a[8] * a[1] * a[14]
+        + a[8] * a[2] * a[13]
+        + a[12] * a[1] * a[10]
+        - a[12] * a[2] * a[9];
+    inv[2] = a[1] * a[6] * a[15] - a[1] * a[7] * a[14] - a[5] * a[2] * a[15]
+        + a[5] * a[3] * a[14]
+        + a[13] * a[2] * a[7]
+        - a[13] * a[3] * a[6];
+    inv[6] = -a[0] * a[6] * a[15] + a[0] * a[7] * a[14] + a[4] * a[2] * a[15]
+        - a[4] * a[3] * a[14]
+        - a[12] * a[2] * a[7]
+        + a[12] * a[3] * a[6];
+    inv[10] = a[0] * a[5] * a[15] - a[0] * a[7] * a[13] - a[4] * a[1] * a[15]
+        + a[4] * a[3] * a[13]
+        + a[12] * a[1] * a[7]
+        - a[12] * a[3] * a[5];
+    inv[14] = -a[0] * a[5] * a[14] + a[0] * a[6] * a[13] + a[4] * a[1] * a[14]
+        - a[4] * a[2] * a[13]
+        - a[12] * a[1] * a[6]
+        + a[12] * a[2] * a[5];
+    inv[3] = -a[1] * a[6] * a[11] + a[1] * a[7] * a[10] + a[5] * a[2] * a[11]
+        - a[5] * a[3] * a[10]
+        - a[9] * a[2] * a[7]
+        + a[9] * a[3] * a[6];
+    inv[7] = a[0] * a[6] * a[11] - a[0] * a[7] * a[10] - a[4] * a[2] * a[11]
+        + a[4] * a[3] * a[10]
+        + a[8] * a[2] * a[7]
+        - a[8] * a[3] * a[6];
+    inv[11] = -a[0] * a[5] * a[11] + a[0] * a[7] * a[9] + a[4] * a[1] * a[11]
+        - a[4] * a[3] * a[9]
+        - a[8] * a[1] * a[7]
+        + a[8] * a[3] * a[5];
+    inv[15] = a[0] * a[5] * a[10] - a[0] * a[6] * a[9] - a[4] * a[1] * a[10]
+        + a[4] * a[2] * a[9]
+        + a[8] * a[1] * a[6]
+        - a[8] * a[2] * a[5];
+
+    let det = a[0] * inv[0] + a[1] * inv[4] + a[2] * inv[8] + a[3] * inv[12];
+    if det == 0.0 || !det.is_finite() {
+        return None;
+    }
+
+    Some(std::array::from_fn(|column| {
+        std::array::from_fn(|row| inv[column * 4 + row] / det)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::GpuContext;
+
+    type Matrix = [[f32; 4]; 4];
+
+    fn multiply(a: Matrix, b: Matrix) -> Matrix {
+        std::array::from_fn(|column| {
+            std::array::from_fn(|row| (0..4).map(|k| a[k][row] * b[column][k]).sum())
+        })
+    }
+
+    /// A right-handed perspective projection with a 90° field of view, aspect 1, near 1, far 10.
+    fn perspective() -> Matrix {
+        let r = 10.0 / (1.0 - 10.0);
+        [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, r, -1.0],
+            [0.0, 0.0, r, 0.0],
+        ]
+    }
+
+    /// A rotation of 30° around y followed by a translation.
+    fn view() -> Matrix {
+        let (sin, cos) = 30f32.to_radians().sin_cos();
+        [
+            [cos, 0.0, -sin, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [sin, 0.0, cos, 0.0],
+            [1.0, -2.0, 3.0, 1.0],
+        ]
+    }
+
+    #[test]
+    fn invert_undoes_the_matrix() {
+        let inverts: [fn(Matrix) -> Option<Matrix>; 2] = [invert, invert_cofactor];
+        for invert in inverts {
+            for m in [view(), perspective(), multiply(perspective(), view())] {
+                let product = multiply(invert(m).unwrap(), m);
+                for column in 0..4 {
+                    for row in 0..4 {
+                        let expected = IDENTITY[column][row];
+                        assert!(
+                            (product[column][row] - expected).abs() < 1e-5,
+                            "{:?}",
+                            product
+                        );
+                    }
+                }
+            }
+
+            let mut singular = IDENTITY;
+            singular[2] = [0.0; 4];
+            assert_eq!(invert(singular), None);
+        }
+    }
+
+    #[test]
+    fn frustum_unprojects_the_clip_space_corners() {
+        let gpu = GpuContext::noop();
+        let target = TargetFormat {
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            depth_format: None,
+            sample_count: 1,
+        };
+        let mut lines = DebugLines::new(&gpu.device, &target, false);
+        lines.frustum(perspective(), Color::WHITE);
+
+        // the near plane is 1 away with a half extent of 1, the far plane 10 away with 10
+        let expected: Vec<[f32; 3]> = (0..8)
+            .map(|i| {
+                let distance = if i & 4 == 0 { 1.0 } else { 10.0 };
+                let x = if i & 1 == 0 { -distance } else { distance };
+                let y = if i & 2 == 0 { -distance } else { distance };
+                [x, y, -distance]
+            })
+            .collect();
+
+        // twelve edges, each between two corners that differ in one bit
+        assert_eq!(lines.vertices.len(), 24);
+        for edge in lines.vertices.chunks(2) {
+            let corner = |position: [f32; 3]| {
+                expected
+                    .iter()
+                    .position(|corner| (0..3).all(|i| (corner[i] - position[i]).abs() < 1e-4))
+                    .unwrap_or_else(|| panic!("{:?} is not a corner", position))
+            };
+            let (a, b) = (corner(edge[0].position), corner(edge[1].position));
+            assert_eq!((a ^ b).count_ones(), 1);
+        }
+
+        lines.clear();
+        let mut singular = IDENTITY;
+        singular[3] = [0.0; 4];
+        lines.frustum(singular, Color::WHITE);
+        assert!(lines.is_empty());
+    }
+
+    #[test]
+    fn shader_is_valid() {
+        crate::std::shader::validate_wgsl(SHADER, "Debug Lines").unwrap();
+    }
+}
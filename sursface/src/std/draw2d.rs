@@ -0,0 +1,585 @@
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, Buffer, Color, CommandEncoder, Device, Queue, RenderPass, RenderPipeline};
+
+use crate::display::Display;
+
+use super::create_labeled_shader;
+use super::target::{load_target, RenderTarget, TargetFormat};
+
+const SHADER: &str = r#"
+struct Screen {
+    size: vec2<f32>,
+    _padding: vec2<f32>,
+};
+
+@group(0) @binding(0) var<uniform> screen: Screen;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) position: vec2<f32>, @location(1) color: vec4<f32>) -> VertexOutput {
+    var output: VertexOutput;
+    let ndc = position / screen.size * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0);
+    output.position = vec4<f32>(ndc, 0.0, 1.0);
+    output.color = color;
+    return output;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    return input.color;
+}
+"#;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Vertex2D {
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Screen {
+    size: [f32; 2],
+    _padding: [f32; 2],
+}
+
+/// Batches 2D shapes in pixel coordinates, `(0, 0)` being the top left of the target, and draws
+/// them in a single alpha-blended draw call. Shapes are drawn in the order they were added.
+///
+/// Call [`Draw2D::prepare`] before the render pass and [`Draw2D::render`] inside it, or
+/// [`Draw2D::flush`] to do both over an existing target. The batch is cleared after each flush.
+pub struct Draw2D {
+    pipeline: RenderPipeline,
+    screen: Buffer,
+    bind_group: BindGroup,
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    vertices: Vec<Vertex2D>,
+    indices: Vec<u32>,
+    /// Indices uploaded by the last `prepare`.
+    prepared: u32,
+}
+
+impl Draw2D {
+    pub fn new(device: &Device, target: &TargetFormat) -> Self {
+        let shader = create_labeled_shader(device, SHADER, "Draw2D");
+
+        let screen = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Draw2D Screen"),
+            contents: bytemuck::cast_slice(&[Screen {
+                size: [1.0; 2],
+                _padding: [0.0; 2],
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Draw2D"),
+            entries: &[super::create_uniform_entry(0)],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Draw2D"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: screen.as_entire_binding(),
+            }],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Draw2D"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<Vertex2D>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4],
+                }],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: target.depth_format.map(|format| wgpu::DepthStencilState {
+                format,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: target.sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            screen,
+            bind_group,
+            vertex_buffer: create_buffer(device, 0, wgpu::BufferUsages::VERTEX),
+            index_buffer: create_buffer(device, 0, wgpu::BufferUsages::INDEX),
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            prepared: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Drops every shape added since the last flush.
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+    }
+
+    /// Adds raw triangles; `indices` are relative to the first of `vertices`.
+    pub fn triangles(&mut self, vertices: &[Vertex2D], indices: &[u32]) {
+        let base = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(vertices);
+        self.indices
+            .extend(indices.iter().map(|index| base + index));
+    }
+
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        self.convex_polygon(
+            &[
+                (x, y),
+                (x + width, y),
+                (x + width, y + height),
+                (x, y + height),
+            ],
+            color,
+        );
+    }
+
+    pub fn rect_outline(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        thickness: f32,
+        color: Color,
+    ) {
+        let t = thickness.min(width / 2.0).min(height / 2.0);
+        self.rect(x, y, width, t, color);
+        self.rect(x, y + height - t, width, t, color);
+        self.rect(x, y + t, t, height - 2.0 * t, color);
+        self.rect(x + width - t, y + t, t, height - 2.0 * t, color);
+    }
+
+    pub fn rounded_rect(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        radius: f32,
+        color: Color,
+    ) {
+        let r = radius.min(width / 2.0).min(height / 2.0).max(0.0);
+        if r == 0.0 {
+            return self.rect(x, y, width, height, color);
+        }
+
+        let corners = [
+            (x + width - r, y + height - r, 0.0),
+            (x + r, y + height - r, 0.5),
+            (x + r, y + r, 1.0),
+            (x + width - r, y + r, 1.5),
+        ];
+        let segments = arc_segments(r, std::f32::consts::FRAC_PI_2);
+
+        let mut points = Vec::with_capacity(4 * (segments + 1));
+        for (cx, cy, start) in corners {
+            for i in 0..=segments {
+                let angle = (start + 0.5 * i as f32 / segments as f32) * std::f32::consts::PI;
+                points.push((cx + r * angle.cos(), cy + r * angle.sin()));
+            }
+        }
+
+        self.convex_polygon(&points, color);
+    }
+
+    pub fn circle(&mut self, center: (f32, f32), radius: f32, color: Color) {
+        let segments = arc_segments(radius, std::f32::consts::TAU);
+        let points: Vec<_> = (0..segments)
+            .map(|i| {
+                let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+                (
+                    center.0 + radius * angle.cos(),
+                    center.1 + radius * angle.sin(),
+                )
+            })
+            .collect();
+
+        self.convex_polygon(&points, color);
+    }
+
+    pub fn circle_outline(
+        &mut self,
+        center: (f32, f32),
+        radius: f32,
+        thickness: f32,
+        color: Color,
+    ) {
+        let segments = arc_segments(radius, std::f32::consts::TAU);
+        let (inner, outer) = (
+            (radius - thickness / 2.0).max(0.0),
+            radius + thickness / 2.0,
+        );
+        let color = to_rgba(color);
+
+        let base = self.vertices.len() as u32;
+        for i in 0..segments {
+            let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+            let (sin, cos) = angle.sin_cos();
+            for r in [inner, outer] {
+                self.vertices.push(Vertex2D {
+                    position: [center.0 + r * cos, center.1 + r * sin],
+                    color,
+                });
+            }
+
+            let (a, b) = (
+                base + 2 * i as u32,
+                base + 2 * ((i as u32 + 1) % segments as u32),
+            );
+            self.indices
+                .extend_from_slice(&[a, a + 1, b + 1, a, b + 1, b]);
+        }
+    }
+
+    pub fn line(&mut self, from: (f32, f32), to: (f32, f32), thickness: f32, color: Color) {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length == 0.0 {
+            return;
+        }
+
+        let (nx, ny) = (
+            -dy / length * thickness / 2.0,
+            dx / length * thickness / 2.0,
+        );
+        self.convex_polygon(
+            &[
+                (from.0 + nx, from.1 + ny),
+                (to.0 + nx, to.1 + ny),
+                (to.0 - nx, to.1 - ny),
+                (from.0 - nx, from.1 - ny),
+            ],
+            color,
+        );
+    }
+
+    /// Connected line segments; closes the loop when `closed` is set.
+    pub fn polyline(&mut self, points: &[(f32, f32)], thickness: f32, closed: bool, color: Color) {
+        for pair in points.windows(2) {
+            self.line(pair[0], pair[1], thickness, color);
+        }
+        if closed && points.len() > 2 {
+            self.line(points[points.len() - 1], points[0], thickness, color);
+        }
+    }
+
+    /// A filled simple polygon, convex or not, with points in either winding order.
+    pub fn polygon(&mut self, points: &[(f32, f32)], color: Color) {
+        if points.len() < 3 {
+            return;
+        }
+
+        let base = self.vertices.len() as u32;
+        let color = to_rgba(color);
+        self.vertices.extend(points.iter().map(|p| Vertex2D {
+            position: [p.0, p.1],
+            color,
+        }));
+
+        for [a, b, c] in triangulate(points) {
+            self.indices
+                .extend_from_slice(&[base + a as u32, base + b as u32, base + c as u32]);
+        }
+    }
+
+    fn convex_polygon(&mut self, points: &[(f32, f32)], color: Color) {
+        let base = self.vertices.len() as u32;
+        let color = to_rgba(color);
+        self.vertices.extend(points.iter().map(|p| Vertex2D {
+            position: [p.0, p.1],
+            color,
+        }));
+
+        for i in 1..points.len().saturating_sub(1) as u32 {
+            self.indices
+                .extend_from_slice(&[base, base + i, base + i + 1]);
+        }
+    }
+
+    /// Uploads the batch, sized for a target of `width` by `height` pixels.
+    pub fn prepare(&mut self, device: &Device, queue: &Queue, width: u32, height: u32) {
+        queue.write_buffer(
+            &self.screen,
+            0,
+            bytemuck::cast_slice(&[Screen {
+                size: [width.max(1) as f32, height.max(1) as f32],
+                _padding: [0.0; 2],
+            }]),
+        );
+
+        let vertices: &[u8] = bytemuck::cast_slice(&self.vertices);
+        if vertices.len() as u64 > self.vertex_buffer.size() {
+            self.vertex_buffer = create_buffer(device, vertices.len(), wgpu::BufferUsages::VERTEX);
+        }
+        let indices: &[u8] = bytemuck::cast_slice(&self.indices);
+        if indices.len() as u64 > self.index_buffer.size() {
+            self.index_buffer = create_buffer(device, indices.len(), wgpu::BufferUsages::INDEX);
+        }
+
+        queue.write_buffer(&self.vertex_buffer, 0, vertices);
+        queue.write_buffer(&self.index_buffer, 0, indices);
+        self.prepared = self.indices.len() as u32;
+    }
+
+    /// Draws what the last [`Draw2D::prepare`] uploaded.
+    pub fn render(&self, rpass: &mut RenderPass) {
+        if self.prepared == 0 {
+            return;
+        }
+
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        rpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        rpass.draw_indexed(0..self.prepared, 0, 0..1);
+    }
+
+    /// Draws the batch over `target` in its own pass and clears it.
+    pub fn flush(
+        &mut self,
+        display: &Display,
+        encoder: &mut CommandEncoder,
+        target: &RenderTarget,
+    ) {
+        if !self.is_empty() {
+            self.prepare(&display.device, &display.queue, target.width, target.height);
+            let mut rpass = load_target(target, encoder);
+            self.render(&mut rpass);
+        }
+        self.clear();
+    }
+}
+
+fn create_buffer(device: &Device, size: usize, usage: wgpu::BufferUsages) -> Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Draw2D"),
+        // keep a minimum size and round up so small growth doesn't reallocate every frame
+        size: (size.max(1024).next_power_of_two()) as u64,
+        usage: usage | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn to_rgba(color: Color) -> [f32; 4] {
+    [
+        color.r as f32,
+        color.g as f32,
+        color.b as f32,
+        color.a as f32,
+    ]
+}
+
+/// Enough segments for an arc of `radius` pixels spanning `angle` radians to look smooth.
+fn arc_segments(radius: f32, angle: f32) -> usize {
+    ((radius.max(0.0).sqrt() * angle * 1.5).ceil() as usize).clamp(4, 256)
+}
+
+/// Ear-clipping triangulation of a simple polygon. Polygons without area produce no triangles.
+fn triangulate(points: &[(f32, f32)]) -> Vec<[usize; 3]> {
+    let area = signed_area(points);
+    if area == 0.0 || !area.is_finite() {
+        return Vec::new();
+    }
+    let orientation = area.signum();
+
+    let cross = |a: (f32, f32), b: (f32, f32), c: (f32, f32)| {
+        ((b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)) * orientation
+    };
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = (
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            );
+            let (pa, pb, pc) = (points[a], points[b], points[c]);
+            if cross(pa, pb, pc) <= 0.0 {
+                return false;
+            }
+
+            remaining.iter().all(|&p| {
+                p == a
+                    || p == b
+                    || p == c
+                    || cross(pa, pb, points[p]) < 0.0
+                    || cross(pb, pc, points[p]) < 0.0
+                    || cross(pc, pa, points[p]) < 0.0
+            })
+        });
+
+        // degenerate or self-intersecting input: clip anything to make progress
+        let i = ear.unwrap_or(0);
+        triangles.push([
+            remaining[(i + n - 1) % n],
+            remaining[i],
+            remaining[(i + 1) % n],
+        ]);
+        remaining.remove(i);
+    }
+
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+/// Twice the area enclosed by `points`, positive when they wind counter-clockwise in a y-up
+/// frame.
+fn signed_area(points: &[(f32, f32)]) -> f32 {
+    (0..points.len())
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that the triangles cover the polygon exactly once, none of them flipped.
+    fn assert_covers(points: &[(f32, f32)]) {
+        let triangles = triangulate(points);
+        assert_eq!(triangles.len(), points.len() - 2, "{:?}", triangles);
+
+        let orientation = signed_area(points).signum();
+        let mut covered = 0.0;
+        for &[a, b, c] in &triangles {
+            let area = signed_area(&[points[a], points[b], points[c]]);
+            assert!(area * orientation >= 0.0, "{:?} is flipped", [a, b, c]);
+            covered += area.abs();
+        }
+        assert_eq!(covered, signed_area(points).abs());
+    }
+
+    fn reversed(points: &[(f32, f32)]) -> Vec<(f32, f32)> {
+        points.iter().rev().copied().collect()
+    }
+
+    #[test]
+    fn triangulates_concave_polygons_in_both_windings() {
+        let l_shape = [
+            (0.0, 0.0),
+            (4.0, 0.0),
+            (4.0, 1.0),
+            (1.0, 1.0),
+            (1.0, 4.0),
+            (0.0, 4.0),
+        ];
+        let star: Vec<(f32, f32)> = (0..10)
+            .map(|i| {
+                let angle = i as f32 * std::f32::consts::TAU / 10.0;
+                let radius = if i % 2 == 0 { 4.0 } else { 1.5 };
+                (radius * angle.cos(), radius * angle.sin())
+            })
+            .collect();
+        let comb = [
+            (0.0, 0.0),
+            (5.0, 0.0),
+            (5.0, 3.0),
+            (4.0, 3.0),
+            (4.0, 1.0),
+            (3.0, 1.0),
+            (3.0, 3.0),
+            (2.0, 3.0),
+            (2.0, 1.0),
+            (1.0, 1.0),
+            (1.0, 3.0),
+            (0.0, 3.0),
+        ];
+
+        for points in [l_shape.to_vec(), comb.to_vec()] {
+            assert_covers(&points);
+            assert_covers(&reversed(&points));
+        }
+
+        // irrational coordinates, so only check the winding and the count
+        for points in [star.clone(), reversed(&star)] {
+            let orientation = signed_area(&points).signum();
+            let triangles = triangulate(&points);
+            assert_eq!(triangles.len(), 8);
+            for [a, b, c] in triangles {
+                assert!(signed_area(&[points[a], points[b], points[c]]) * orientation > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn triangulates_around_collinear_points() {
+        let square_with_midpoints = [
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (2.0, 0.0),
+            (2.0, 1.0),
+            (2.0, 2.0),
+            (1.0, 2.0),
+            (0.0, 2.0),
+            (0.0, 1.0),
+        ];
+        assert_covers(&square_with_midpoints);
+        assert_covers(&reversed(&square_with_midpoints));
+
+        let repeated_point = [(0.0, 0.0), (2.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)];
+        assert_covers(&repeated_point);
+    }
+
+    #[test]
+    fn degenerate_polygons_have_no_triangles() {
+        assert!(triangulate(&[(0.0, 0.0), (1.0, 1.0), (2.0, 2.0), (3.0, 3.0)]).is_empty());
+        assert!(triangulate(&[(1.0, 1.0); 3]).is_empty());
+        assert!(triangulate(&[(0.0, 0.0), (f32::NAN, 1.0), (1.0, 0.0)]).is_empty());
+    }
+
+    #[test]
+    fn shader_is_valid() {
+        crate::std::shader::validate_wgsl(SHADER, "Draw2D").unwrap();
+    }
+}
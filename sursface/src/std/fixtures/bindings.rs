@@ -0,0 +1,74 @@
+// Generated by sursface from fixtures/bindings.wgsl. Do not edit.
+
+pub const UNIFORMS_GROUP: u32 = 0;
+pub const UNIFORMS_BINDING: u32 = 0;
+pub const UNIFORMS_LAYOUT_ENTRY: sursface::wgpu::BindGroupLayoutEntry =
+    sursface::wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: sursface::wgpu::ShaderStages::VERTEX.union(sursface::wgpu::ShaderStages::FRAGMENT),
+        ty: sursface::wgpu::BindingType::Buffer {
+            ty: sursface::wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: sursface::wgpu::BufferSize::new(192),
+        },
+        count: None,
+    };
+
+pub const INSTANCES_GROUP: u32 = 1;
+pub const INSTANCES_BINDING: u32 = 0;
+pub const INSTANCES_LAYOUT_ENTRY: sursface::wgpu::BindGroupLayoutEntry =
+    sursface::wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: sursface::wgpu::ShaderStages::VERTEX,
+        ty: sursface::wgpu::BindingType::Buffer {
+            ty: sursface::wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: sursface::wgpu::BufferSize::new(48),
+        },
+        count: None,
+    };
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[allow(non_snake_case)]
+pub struct Light {
+    pub direction: [f32; 3],
+    pub _pad0: [u32; 1],
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+const _: () = assert!(::core::mem::size_of::<Light>() == 32);
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[allow(non_snake_case)]
+pub struct Uniforms {
+    pub view_proj: [[f32; 4]; 4],
+    pub normal: [[f32; 4]; 3],
+    pub corners: [[f32; 4]; 2],
+    pub light: Light,
+    pub time: f32,
+    pub _pad0: [u32; 3],
+}
+const _: () = assert!(::core::mem::size_of::<Uniforms>() == 192);
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[allow(non_snake_case)]
+pub struct Instance {
+    pub offset: [f32; 3],
+    pub scale: f32,
+    pub tint: [f32; 2],
+    pub _pad0: [u32; 2],
+}
+const _: () = assert!(::core::mem::size_of::<Instance>() == 32);
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[allow(non_snake_case)]
+pub struct Instances {
+    pub count: u32,
+    pub _pad0: [u32; 3],
+    // `items` is a runtime-sized array of `Instance` starting at byte 16
+}
+const _: () = assert!(::core::mem::size_of::<Instances>() == 16);
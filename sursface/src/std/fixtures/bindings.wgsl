@@ -0,0 +1,38 @@
+struct Light {
+    direction: vec3<f32>,
+    color: vec3<f32>,
+    intensity: f32,
+}
+
+struct Uniforms {
+    view_proj: mat4x4<f32>,
+    normal: mat3x3<f32>,
+    corners: array<vec3<f32>, 2>,
+    light: Light,
+    time: f32,
+}
+
+struct Instance {
+    offset: vec3<f32>,
+    scale: f32,
+    tint: vec2<f32>,
+}
+
+struct Instances {
+    count: u32,
+    items: array<Instance>,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+@group(1) @binding(0) var<storage, read> instances: Instances;
+
+@vertex
+fn vs_main(@builtin(instance_index) index: u32) -> @builtin(position) vec4<f32> {
+    let instance = instances.items[index];
+    return uniforms.view_proj * vec4<f32>(uniforms.normal * instance.offset * instance.scale, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(uniforms.light.color * uniforms.light.intensity, uniforms.time);
+}
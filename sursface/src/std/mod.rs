@@ -2,14 +2,29 @@ use image::{GenericImageView, ImageFormat};
 use wgpu::util::DeviceExt;
 use wgpu::{
     BindGroup, BindGroupEntry, BindGroupLayout, BindGroupLayoutEntry, Buffer, Color,
-    CommandEncoder, Device, Face, FrontFace, PipelineLayout, PolygonMode, PrimitiveState,
-    PrimitiveTopology, Queue, RenderPass, RenderPipeline, Sampler, ShaderModule, Surface,
-    SurfaceTexture, TextureView, VertexBufferLayout,
+    CommandEncoder, Device, PipelineLayout, Queue, RenderPass, RenderPipeline, Sampler,
+    ShaderModule, Surface, SurfaceTexture, TextureView, VertexBufferLayout,
 };
 
 use crate::display::Display;
 
+use self::assets::AssetError;
+use self::source::AssetSource;
+
+pub mod assets;
+pub mod atlas;
+pub mod debug_lines;
+pub mod draw2d;
 pub mod models;
+pub mod post;
+pub mod preprocess;
+pub mod reflect;
+pub mod shader;
+pub mod source;
+pub mod sprite;
+pub mod target;
+#[cfg(feature = "text")]
+pub mod text;
 
 pub fn get_framebuffer(surface: &Surface) -> (SurfaceTexture, TextureView) {
     let output = surface.get_current_texture().unwrap();
@@ -43,10 +58,43 @@ pub fn clear<'a>(
 }
 
 pub fn create_shader(device: &Device, shader_source: &str) -> ShaderModule {
+    create_labeled_shader(device, shader_source, "shader")
+}
+
+/// Like [`create_shader`], with `label` naming the shader in validation errors and wgpu's
+/// debug output.
+pub fn create_labeled_shader(device: &Device, shader_source: &str, label: &str) -> ShaderModule {
+    if shader::shader_validation_enabled() {
+        if let Err(e) = shader::validate_wgsl(shader_source, label) {
+            log::error!("Shader validation failed:\n{}", e);
+            panic!("Shader validation failed: {}", e.message);
+        }
+    }
+
+    create_shader_module(device, shader_source, label)
+}
+
+/// Like [`create_shader`], with the source read from `path`. The shader is always validated, and
+/// invalid WGSL is returned as [`assets::AssetErrorKind::Shader`] instead of panicking.
+pub fn load_shader(
+    device: &Device,
+    source: &dyn AssetSource,
+    path: &str,
+) -> Result<ShaderModule, AssetError> {
+    let shader_source = source.read_to_string(path)?;
+    shader::validate_wgsl(&shader_source, path).map_err(|e| AssetError::shader(path, e))?;
+    Ok(create_shader_module(device, &shader_source, path))
+}
+
+pub(crate) fn create_shader_module(
+    device: &Device,
+    shader_source: &str,
+    label: &str,
+) -> ShaderModule {
     use std::borrow::Cow;
 
     device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: None,
+        label: Some(label),
         source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(shader_source)),
     })
 }
@@ -57,46 +105,50 @@ pub fn create_render_pipeline<'a>(
     shader: ShaderModule,
     buffers: &[VertexBufferLayout<'a>],
 ) -> RenderPipeline {
-    display
-        .device
-        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers,
-                compilation_options: Default::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(display.config.format.into())],
-                compilation_options: Default::default(),
-            }),
-            primitive: PrimitiveState {
-                topology: PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: FrontFace::Ccw,
-                cull_mode: Some(Face::Back),
-                polygon_mode: PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
-            cache: None,
-        })
+    target::create_target_pipeline(
+        &display.device,
+        &display.into(),
+        pipeline_layout,
+        shader,
+        buffers,
+    )
 }
 
 pub fn create_texture_layout_entry_from_image(
     device: &Device,
     queue: &Queue,
-    image_bytes: &'static [u8],
+    image_bytes: &[u8],
+    binding_index: u32,
+) -> (BindGroupLayoutEntry, TextureView) {
+    let img = image::load(std::io::Cursor::new(image_bytes), ImageFormat::Png).unwrap();
+    upload_texture_layout_entry(device, queue, &img, binding_index)
+}
+
+/// Like [`create_texture_layout_entry_from_image`], with the PNG read from `path`.
+pub fn load_texture_layout_entry(
+    device: &Device,
+    queue: &Queue,
+    source: &dyn AssetSource,
+    path: &str,
+    binding_index: u32,
+) -> Result<(BindGroupLayoutEntry, TextureView), AssetError> {
+    let bytes = source.read(path)?;
+    let img = image::load(std::io::Cursor::new(&bytes[..]), ImageFormat::Png)
+        .map_err(|e| AssetError::new(path, e))?;
+    Ok(upload_texture_layout_entry(
+        device,
+        queue,
+        &img,
+        binding_index,
+    ))
+}
+
+fn upload_texture_layout_entry(
+    device: &Device,
+    queue: &Queue,
+    img: &image::DynamicImage,
     binding_index: u32,
 ) -> (BindGroupLayoutEntry, TextureView) {
-    let img = image::load(std::io::Cursor::new(image_bytes.as_ref()), ImageFormat::Png).unwrap();
     let rgba = img.to_rgba8();
     let dimensions = img.dimensions();
 
@@ -280,3 +332,62 @@ where
         uniform_bind_group,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::GpuContext;
+    use crate::std::source::MemorySource;
+
+    #[test]
+    fn load_shader_reads_from_source() {
+        let gpu = GpuContext::noop();
+        let source = MemorySource::new().with(
+            "shader.wgsl",
+            "@fragment fn fs_main() -> @location(0) vec4<f32> { return vec4<f32>(1.0); }",
+        );
+
+        assert!(load_shader(&gpu.device, &source, "shader.wgsl").is_ok());
+        let error = load_shader(&gpu.device, &source, "missing.wgsl").unwrap_err();
+        assert_eq!(error.path, "missing.wgsl");
+        assert!(matches!(error.kind, assets::AssetErrorKind::Other));
+    }
+
+    #[test]
+    fn load_shader_reports_invalid_wgsl() {
+        let gpu = GpuContext::noop();
+        let source = MemorySource::new().with(
+            "broken.wgsl",
+            "@fragment fn fs_main() -> @location(0) vec4<f32> {\n    return missing;\n}",
+        );
+
+        let error = load_shader(&gpu.device, &source, "broken.wgsl").unwrap_err();
+        assert_eq!(error.path, "broken.wgsl");
+        match error.kind {
+            assets::AssetErrorKind::Shader(shader) => {
+                assert_eq!(shader.label, "broken.wgsl");
+                assert_eq!(shader.location.map(|(line, _)| line), Some(2));
+            }
+            kind => panic!("expected a shader error, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn load_texture_layout_entry_reports_decode_errors() {
+        let gpu = GpuContext::noop();
+        let mut png = Vec::new();
+        image::RgbaImage::new(2, 2)
+            .write_to(&mut std::io::Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        let source = MemorySource::new()
+            .with("image.png", png)
+            .with("broken.png", b"not a png".to_vec());
+
+        let (entry, _) =
+            load_texture_layout_entry(&gpu.device, &gpu.queue, &source, "image.png", 3).unwrap();
+        assert_eq!(entry.binding, 3);
+        let error = load_texture_layout_entry(&gpu.device, &gpu.queue, &source, "broken.png", 0)
+            .unwrap_err();
+        assert_eq!(error.path, "broken.png");
+    }
+}
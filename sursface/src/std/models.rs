@@ -15,6 +15,16 @@ pub struct VertexPositionNormalUv {
     uv: [f32; 2],
 }
 
+impl VertexPositionNormalUv {
+    pub const fn new(position: [f32; 3], normal: [f32; 3], uv: [f32; 2]) -> Self {
+        Self {
+            position,
+            normal,
+            uv,
+        }
+    }
+}
+
 pub const fn quad_uvs(top_left: (f32, f32), bottom_right: (f32, f32)) -> [[f32; 2]; 4] {
     [
         [top_left.0, top_left.1],
@@ -0,0 +1,552 @@
+use std::fmt;
+
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, CommandEncoder, Device, Queue, RenderPass, RenderPipeline,
+    Sampler, TextureFormat,
+};
+
+use crate::display::Display;
+
+use super::create_labeled_shader;
+use super::target::{clear_target, RenderTarget, TargetFormat};
+
+/// Vertex stage shared by every fullscreen pass. `uv` is `(0, 0)` at the top left.
+pub const FULLSCREEN_VERTEX: &str = r#"
+struct FullscreenOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var output: FullscreenOutput;
+    output.position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    output.uv = uv;
+    return output;
+}
+"#;
+
+/// Bindings available to every post-processing effect.
+pub const POST_BINDINGS: &str = r#"
+struct PostParams {
+    texel_size: vec2<f32>,
+    time: f32,
+    _padding: f32,
+};
+
+@group(0) @binding(0) var t_input: texture_2d<f32>;
+@group(0) @binding(1) var s_input: sampler;
+@group(0) @binding(2) var<uniform> post: PostParams;
+"#;
+
+/// Draws a single triangle covering the whole target, without any vertex buffer.
+pub struct FullscreenPass {
+    pub pipeline: RenderPipeline,
+}
+
+impl FullscreenPass {
+    /// `fragment_source` must define `fs_main(input: FullscreenOutput) -> @location(0) vec4<f32>`.
+    pub fn new(
+        device: &Device,
+        target: &TargetFormat,
+        fragment_source: &str,
+        bind_group_layouts: &[&BindGroupLayout],
+    ) -> Self {
+        let shader = create_labeled_shader(
+            device,
+            &format!("{}{}", FULLSCREEN_VERTEX, fragment_source),
+            "Fullscreen Pass",
+        );
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Fullscreen Pass"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(target.format.into())],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: target.depth_format.map(|format| wgpu::DepthStencilState {
+                format,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: target.sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        Self { pipeline }
+    }
+
+    pub fn draw(&self, rpass: &mut RenderPass, bind_groups: &[&BindGroup]) {
+        rpass.set_pipeline(&self.pipeline);
+        for (index, bind_group) in bind_groups.iter().enumerate() {
+            rpass.set_bind_group(index as u32, *bind_group, &[]);
+        }
+        rpass.draw(0..3, 0..1);
+    }
+}
+
+pub enum Effect {
+    /// ACES filmic tonemapping of HDR input.
+    Tonemap,
+    Fxaa,
+    Vignette,
+    /// WGSL defining `fs_main(input: FullscreenOutput)`, with [`POST_BINDINGS`] in scope.
+    Custom(String),
+}
+
+impl Effect {
+    fn source(&self) -> &str {
+        match self {
+            Effect::Tonemap => TONEMAP,
+            Effect::Fxaa => FXAA,
+            Effect::Vignette => VIGNETTE,
+            Effect::Custom(source) => source,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostParams {
+    texel_size: [f32; 2],
+    time: f32,
+    _padding: f32,
+}
+
+/// Renders the scene into an offscreen target, then runs each effect in order, ping-ponging
+/// between two intermediates. The last effect writes into the output target.
+pub struct PostProcessChain {
+    passes: Vec<FullscreenPass>,
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    params: Buffer,
+    format: TextureFormat,
+    output: TargetFormat,
+    targets: [RenderTarget; 2],
+    bind_groups: [BindGroup; 2],
+}
+
+impl PostProcessChain {
+    /// `format` is used for the scene and intermediate targets, e.g. `Rgba16Float` for HDR.
+    pub fn new(
+        display: &Display,
+        format: TextureFormat,
+        output: &TargetFormat,
+        effects: &[Effect],
+    ) -> Self {
+        Self::with_size(
+            &display.device,
+            display.config.width,
+            display.config.height,
+            format,
+            output,
+            effects,
+        )
+    }
+
+    /// Like [`PostProcessChain::new`], with `width` by `height` intermediates instead of the
+    /// display's size, e.g. for offscreen rendering.
+    pub fn with_size(
+        device: &Device,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        output: &TargetFormat,
+        effects: &[Effect],
+    ) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Post Process"),
+            entries: &[
+                RenderTarget::layout_entry(0),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post Params"),
+            contents: bytemuck::cast_slice(&[PostParams {
+                texel_size: [0.0; 2],
+                time: 0.0,
+                _padding: 0.0,
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let intermediate = TargetFormat {
+            format,
+            depth_format: None,
+            sample_count: 1,
+        };
+        let output = *output;
+
+        // Without effects the scene still has to be copied into the output.
+        let effects = if effects.is_empty() {
+            &[Effect::Custom(PASSTHROUGH.to_owned())][..]
+        } else {
+            effects
+        };
+        let passes = effects
+            .iter()
+            .enumerate()
+            .map(|(index, effect)| {
+                let target = if index + 1 == effects.len() {
+                    &output
+                } else {
+                    &intermediate
+                };
+                let source = format!("{}{}", POST_BINDINGS, effect.source());
+                FullscreenPass::new(device, target, &source, &[&layout])
+            })
+            .collect();
+
+        let targets = [
+            RenderTarget::offscreen(device, width, height, format),
+            RenderTarget::offscreen(device, width, height, format),
+        ];
+        let bind_groups = [
+            Self::create_bind_group(device, &layout, &targets[0], &sampler, &params),
+            Self::create_bind_group(device, &layout, &targets[1], &sampler, &params),
+        ];
+
+        Self {
+            passes,
+            layout,
+            sampler,
+            params,
+            format,
+            output,
+            targets,
+            bind_groups,
+        }
+    }
+
+    fn create_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        target: &RenderTarget,
+        sampler: &Sampler,
+        params: &Buffer,
+    ) -> BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(target.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    pub fn resize(&mut self, device: &Device, width: u32, height: u32) {
+        if width == 0 || height == 0 || (width, height) == self.size() {
+            return;
+        }
+
+        self.targets = [
+            RenderTarget::offscreen(device, width, height, self.format),
+            RenderTarget::offscreen(device, width, height, self.format),
+        ];
+        self.bind_groups = [0, 1].map(|i| {
+            Self::create_bind_group(
+                device,
+                &self.layout,
+                &self.targets[i],
+                &self.sampler,
+                &self.params,
+            )
+        });
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.targets[0].width, self.targets[0].height)
+    }
+
+    /// The target the scene should be drawn into this frame, resized to match the display.
+    pub fn scene_target(&mut self, display: &Display) -> &RenderTarget {
+        self.resize(&display.device, display.config.width, display.config.height);
+        &self.targets[0]
+    }
+
+    /// Runs the effects on the scene target and writes the result into `output`, which must have
+    /// the format the chain was created with.
+    pub fn apply(
+        &self,
+        display: &Display,
+        encoder: &mut CommandEncoder,
+        output: &RenderTarget,
+    ) -> Result<(), FormatMismatch> {
+        self.apply_with_queue(&display.queue, encoder, output)
+    }
+
+    /// Like [`PostProcessChain::apply`], writing the effect parameters through `queue`.
+    pub fn apply_with_queue(
+        &self,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        output: &RenderTarget,
+    ) -> Result<(), FormatMismatch> {
+        if output.target_format() != self.output {
+            return Err(FormatMismatch {
+                expected: self.output,
+                found: output.target_format(),
+            });
+        }
+
+        let (width, height) = self.size();
+        queue.write_buffer(
+            &self.params,
+            0,
+            bytemuck::cast_slice(&[PostParams {
+                texel_size: [1.0 / width as f32, 1.0 / height as f32],
+                time: crate::time::now_secs(),
+                _padding: 0.0,
+            }]),
+        );
+
+        for (index, pass) in self.passes.iter().enumerate() {
+            let source = index % 2;
+            let destination = if index + 1 == self.passes.len() {
+                output
+            } else {
+                &self.targets[1 - source]
+            };
+
+            let mut rpass = clear_target(destination, encoder, wgpu::Color::BLACK);
+            pass.draw(&mut rpass, &[&self.bind_groups[source]]);
+        }
+
+        Ok(())
+    }
+}
+
+/// The output passed to [`PostProcessChain::apply`] doesn't match the one it was created for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FormatMismatch {
+    pub expected: TargetFormat,
+    pub found: TargetFormat,
+}
+
+impl fmt::Display for FormatMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "post-process chain was created for {:?} but applied to {:?}",
+            self.expected, self.found
+        )
+    }
+}
+
+impl std::error::Error for FormatMismatch {}
+
+const PASSTHROUGH: &str = r#"
+@fragment
+fn fs_main(input: FullscreenOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_input, s_input, input.uv);
+}
+"#;
+
+const TONEMAP: &str = r#"
+@fragment
+fn fs_main(input: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_input, s_input, input.uv);
+    let x = color.rgb;
+    let mapped = clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), vec3<f32>(0.0), vec3<f32>(1.0));
+    return vec4<f32>(mapped, color.a);
+}
+"#;
+
+const VIGNETTE: &str = r#"
+@fragment
+fn fs_main(input: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_input, s_input, input.uv);
+    let offset = input.uv - vec2<f32>(0.5);
+    let vignette = smoothstep(0.8, 0.25, length(offset));
+    return vec4<f32>(color.rgb * vignette, color.a);
+}
+"#;
+
+const FXAA: &str = r#"
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+@fragment
+fn fs_main(input: FullscreenOutput) -> @location(0) vec4<f32> {
+    let texel = post.texel_size;
+    let center = textureSample(t_input, s_input, input.uv);
+    let luma_nw = luma(textureSample(t_input, s_input, input.uv + vec2<f32>(-1.0, -1.0) * texel).rgb);
+    let luma_ne = luma(textureSample(t_input, s_input, input.uv + vec2<f32>(1.0, -1.0) * texel).rgb);
+    let luma_sw = luma(textureSample(t_input, s_input, input.uv + vec2<f32>(-1.0, 1.0) * texel).rgb);
+    let luma_se = luma(textureSample(t_input, s_input, input.uv + vec2<f32>(1.0, 1.0) * texel).rgb);
+    let luma_m = luma(center.rgb);
+
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    var direction = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.03125, 1.0 / 128.0);
+    let scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, vec2<f32>(-8.0), vec2<f32>(8.0)) * texel;
+
+    let a = 0.5 * (
+        textureSample(t_input, s_input, input.uv + direction * (1.0 / 3.0 - 0.5)).rgb +
+        textureSample(t_input, s_input, input.uv + direction * (2.0 / 3.0 - 0.5)).rgb
+    );
+    let b = a * 0.5 + 0.25 * (
+        textureSample(t_input, s_input, input.uv - direction * 0.5).rgb +
+        textureSample(t_input, s_input, input.uv + direction * 0.5).rgb
+    );
+
+    let luma_b = luma(b);
+    if (luma_b < luma_min || luma_b > luma_max) {
+        return vec4<f32>(a, center.a);
+    }
+    return vec4<f32>(b, center.a);
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::GpuContext;
+    use crate::std::shader::validate_wgsl;
+
+    #[test]
+    fn shipped_effects_are_valid() {
+        for (name, effect) in [
+            ("passthrough", PASSTHROUGH),
+            ("tonemap", TONEMAP),
+            ("vignette", VIGNETTE),
+            ("fxaa", FXAA),
+        ] {
+            let source = format!("{}{}{}", FULLSCREEN_VERTEX, POST_BINDINGS, effect);
+            if let Err(e) = validate_wgsl(&source, name) {
+                panic!("{}", e);
+            }
+        }
+    }
+
+    #[test]
+    fn chain_ping_pongs_into_the_output() {
+        let gpu = GpuContext::noop();
+        let output = TargetFormat {
+            format: TextureFormat::Rgba8UnormSrgb,
+            depth_format: None,
+            sample_count: 1,
+        };
+        let mut chain = PostProcessChain::with_size(
+            &gpu.device,
+            64,
+            32,
+            TextureFormat::Rgba16Float,
+            &output,
+            &[Effect::Tonemap, Effect::Fxaa, Effect::Vignette],
+        );
+        assert_eq!(chain.size(), (64, 32));
+
+        chain.resize(&gpu.device, 0, 10);
+        assert_eq!(chain.size(), (64, 32));
+        chain.resize(&gpu.device, 20, 10);
+        assert_eq!(chain.size(), (20, 10));
+
+        // reading and writing the same texture in one pass would fail wgpu's validation
+        let target = RenderTarget::offscreen(&gpu.device, 20, 10, output.format);
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        chain
+            .apply_with_queue(&gpu.queue, &mut encoder, &target)
+            .unwrap();
+        gpu.queue.submit([encoder.finish()]);
+    }
+
+    #[test]
+    fn chain_rejects_a_mismatched_output() {
+        let gpu = GpuContext::noop();
+        let output = TargetFormat {
+            format: TextureFormat::Bgra8UnormSrgb,
+            depth_format: None,
+            sample_count: 1,
+        };
+        let chain = PostProcessChain::with_size(
+            &gpu.device,
+            16,
+            16,
+            TextureFormat::Rgba8Unorm,
+            &output,
+            &[],
+        );
+
+        let target = RenderTarget::offscreen(&gpu.device, 16, 16, TextureFormat::Rgba8UnormSrgb);
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        assert_eq!(
+            chain.apply_with_queue(&gpu.queue, &mut encoder, &target),
+            Err(FormatMismatch {
+                expected: output,
+                found: target.target_format(),
+            })
+        );
+    }
+}
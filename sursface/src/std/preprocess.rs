@@ -0,0 +1,432 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use wgpu::{Device, ShaderModule};
+
+use super::shader::{self, ShaderError};
+
+/// Where `#include` directives are resolved from.
+pub trait IncludeSource {
+    fn read(&self, path: &str) -> Option<Cow<'_, str>>;
+}
+
+impl IncludeSource for HashMap<String, String> {
+    fn read(&self, path: &str) -> Option<Cow<'_, str>> {
+        self.get(path).map(|s| Cow::Borrowed(s.as_str()))
+    }
+}
+
+/// Embedded sources, e.g. `&[("common.wgsl", include_str!("assets/common.wgsl"))]`.
+impl IncludeSource for [(&str, &str)] {
+    fn read(&self, path: &str) -> Option<Cow<'_, str>> {
+        self.iter()
+            .find(|(name, _)| *name == path)
+            .map(|(_, source)| Cow::Borrowed(*source))
+    }
+}
+
+impl<const N: usize> IncludeSource for [(&str, &str); N] {
+    fn read(&self, path: &str) -> Option<Cow<'_, str>> {
+        self.as_slice().read(path)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use super::source::DirSource;
+
+#[derive(Clone, Debug)]
+pub struct PreprocessError {
+    pub file: String,
+    pub line: u32,
+    pub message: String,
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl std::error::Error for PreprocessError {}
+
+/// Maps lines of the preprocessed output back to the file and line they came from.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    files: Vec<String>,
+    lines: Vec<(usize, u32)>,
+}
+
+impl SourceMap {
+    /// Takes a 1-based output line and returns the originating file and 1-based line.
+    pub fn locate(&self, line: u32) -> Option<(&str, u32)> {
+        let (file, line) = *self.lines.get(line.checked_sub(1)? as usize)?;
+        Some((self.files[file].as_str(), line))
+    }
+
+    fn file_index(&mut self, file: &str) -> usize {
+        match self.files.iter().position(|f| f == file) {
+            Some(index) => index,
+            None => {
+                self.files.push(file.to_owned());
+                self.files.len() - 1
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PreprocessedShader {
+    pub source: String,
+    pub source_map: SourceMap,
+}
+
+impl PreprocessedShader {
+    /// Like [`shader::validate_wgsl`], but the error points at the original file and line.
+    pub fn validate(&self, label: &str) -> Result<naga::Module, ShaderError> {
+        shader::validate_wgsl(&self.source, label).map_err(|mut e| {
+            if let Some((line, column)) = e.location {
+                if let Some((file, original_line)) = self.source_map.locate(line) {
+                    e.diagnostic.push_str(&format!(
+                        "note: line {} of the preprocessed shader comes from {}:{}:{}\n",
+                        line, file, original_line, column
+                    ));
+                    e.label = file.to_owned();
+                    e.location = Some((original_line, column));
+                }
+            }
+            e
+        })
+    }
+
+    pub fn create_shader(&self, device: &Device, label: &str) -> ShaderModule {
+        if shader::shader_validation_enabled() {
+            if let Err(e) = self.validate(label) {
+                log::error!("Shader validation failed:\n{}", e);
+                panic!("Shader validation failed: {}", e.message);
+            }
+        }
+
+        super::create_shader_module(device, &self.source, label)
+    }
+}
+
+struct Conditional {
+    parent_active: bool,
+    taken: bool,
+    active: bool,
+    in_else: bool,
+}
+
+/// Resolves `#include "file.wgsl"`, `#define`, `#undef`, `#ifdef`, `#ifndef`, `#else` and
+/// `#endif`. Each file is included at most once, so shared structs can be included freely.
+pub struct Preprocessor<'a> {
+    source: &'a dyn IncludeSource,
+    defines: HashMap<String, String>,
+}
+
+impl<'a> Preprocessor<'a> {
+    pub fn new(source: &'a dyn IncludeSource) -> Self {
+        Self {
+            source,
+            defines: HashMap::new(),
+        }
+    }
+
+    pub fn define(mut self, name: &str, value: &str) -> Self {
+        self.defines.insert(name.to_owned(), value.to_owned());
+        self
+    }
+
+    pub fn process(&self, path: &str) -> Result<PreprocessedShader, PreprocessError> {
+        let mut state = State {
+            defines: self.defines.clone(),
+            included: HashSet::new(),
+            stack: Vec::new(),
+            output: String::new(),
+            source_map: SourceMap::default(),
+        };
+
+        let source = self.source.read(path).ok_or_else(|| PreprocessError {
+            file: path.to_owned(),
+            line: 0,
+            message: "file not found".to_owned(),
+        })?;
+        state.process_file(self.source, path, &source)?;
+
+        Ok(PreprocessedShader {
+            source: state.output,
+            source_map: state.source_map,
+        })
+    }
+}
+
+struct State {
+    defines: HashMap<String, String>,
+    included: HashSet<String>,
+    stack: Vec<String>,
+    output: String,
+    source_map: SourceMap,
+}
+
+impl State {
+    fn process_file(
+        &mut self,
+        includes: &dyn IncludeSource,
+        path: &str,
+        source: &str,
+    ) -> Result<(), PreprocessError> {
+        self.included.insert(path.to_owned());
+        self.stack.push(path.to_owned());
+        let file_index = self.source_map.file_index(path);
+        let mut conditionals: Vec<Conditional> = Vec::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index as u32 + 1;
+            let error = |message: &str| PreprocessError {
+                file: path.to_owned(),
+                line: line_number,
+                message: message.to_owned(),
+            };
+            let active = conditionals.last().is_none_or(|c| c.active);
+
+            let Some(directive) = line.trim_start().strip_prefix('#') else {
+                if active {
+                    self.output.push_str(&substitute(line, &self.defines));
+                    self.output.push('\n');
+                    self.source_map.lines.push((file_index, line_number));
+                }
+                continue;
+            };
+
+            let (name, rest) = directive
+                .split_once(char::is_whitespace)
+                .unwrap_or((directive, ""));
+            let rest = rest.trim();
+
+            match name {
+                "ifdef" | "ifndef" => {
+                    let defined = self.defines.contains_key(rest);
+                    let taken = active && (defined == (name == "ifdef"));
+                    conditionals.push(Conditional {
+                        parent_active: active,
+                        taken,
+                        active: taken,
+                        in_else: false,
+                    });
+                }
+                "else" => {
+                    let c = conditionals
+                        .last_mut()
+                        .ok_or_else(|| error("#else without #ifdef"))?;
+                    if c.in_else {
+                        return Err(error("second #else for the same #ifdef"));
+                    }
+                    c.in_else = true;
+                    c.active = c.parent_active && !c.taken;
+                    c.taken = true;
+                }
+                "endif" => {
+                    conditionals
+                        .pop()
+                        .ok_or_else(|| error("#endif without #ifdef"))?;
+                }
+                _ if !active => (),
+                "define" => {
+                    let (key, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                    if key.is_empty() {
+                        return Err(error("#define without a name"));
+                    }
+                    self.defines.insert(key.to_owned(), value.trim().to_owned());
+                }
+                "undef" => {
+                    self.defines.remove(rest);
+                }
+                "include" => {
+                    let target = rest
+                        .strip_prefix('"')
+                        .and_then(|r| r.strip_suffix('"'))
+                        .ok_or_else(|| error("expected #include \"file\""))?;
+                    let target = resolve_relative(path, target);
+
+                    if self.stack.contains(&target) {
+                        return Err(error(&format!("recursive include of {}", target)));
+                    }
+                    if self.included.contains(&target) {
+                        continue;
+                    }
+
+                    let included = includes
+                        .read(&target)
+                        .ok_or_else(|| error(&format!("cannot find {}", target)))?;
+                    self.process_file(includes, &target, &included)?;
+                }
+                _ => return Err(error(&format!("unknown directive #{}", name))),
+            }
+        }
+
+        if !conditionals.is_empty() {
+            return Err(PreprocessError {
+                file: path.to_owned(),
+                line: source.lines().count() as u32,
+                message: "unterminated #ifdef".to_owned(),
+            });
+        }
+
+        self.stack.pop();
+        Ok(())
+    }
+}
+
+fn resolve_relative(from: &str, target: &str) -> String {
+    let mut parts: Vec<&str> = from.split('/').collect();
+    parts.pop();
+
+    for part in target.split('/') {
+        match part {
+            "." | "" => (),
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+
+    parts.join("/")
+}
+
+fn substitute<'s>(line: &'s str, defines: &HashMap<String, String>) -> Cow<'s, str> {
+    if defines.is_empty() {
+        return Cow::Borrowed(line);
+    }
+
+    let mut result = String::with_capacity(line.len());
+    let mut rest = line;
+
+    while let Some(start) = rest.find(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        let (before, from_ident) = rest.split_at(start);
+        let end = from_ident
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(from_ident.len());
+        let (ident, after) = from_ident.split_at(end);
+
+        result.push_str(before);
+        // identifiers glued to digits (e.g. `1u`) are literal suffixes, not names
+        let is_suffix = before.ends_with(|c: char| c.is_ascii_digit());
+        match defines.get(ident) {
+            Some(value) if !is_suffix => result.push_str(value),
+            _ => result.push_str(ident),
+        }
+        rest = after;
+    }
+
+    result.push_str(rest);
+    Cow::Owned(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(
+        files: &impl IncludeSource,
+        path: &str,
+    ) -> Result<PreprocessedShader, PreprocessError> {
+        Preprocessor::new(files).process(path)
+    }
+
+    #[test]
+    fn includes_each_file_once() {
+        let files = [
+            (
+                "main.wgsl",
+                "#include \"lib/a.wgsl\"\n#include \"lib/b.wgsl\"\nmain",
+            ),
+            ("lib/a.wgsl", "#include \"common.wgsl\"\na"),
+            ("lib/b.wgsl", "#include \"./common.wgsl\"\nb"),
+            ("lib/common.wgsl", "common"),
+        ];
+        let shader = process(&files, "main.wgsl").unwrap();
+        assert_eq!(shader.source, "common\na\nb\nmain\n");
+
+        let files = [
+            ("a.wgsl", "#include \"b.wgsl\""),
+            ("b.wgsl", "#include \"a.wgsl\""),
+        ];
+        let error = process(&files, "a.wgsl").unwrap_err();
+        assert_eq!((error.file.as_str(), error.line), ("b.wgsl", 1));
+        assert_eq!(error.message, "recursive include of a.wgsl");
+    }
+
+    #[test]
+    fn nested_conditionals() {
+        let source = "\
+#ifdef A
+a
+#ifndef B
+a_not_b
+#else
+a_b
+#endif
+#else
+not_a
+#ifdef B
+not_a_b
+#endif
+#endif
+end";
+        let files = [("main.wgsl", source)];
+        let run = |defines: &[&str]| {
+            let preprocessor = defines
+                .iter()
+                .fold(Preprocessor::new(&files), |p, name| p.define(name, ""));
+            preprocessor.process("main.wgsl").unwrap().source
+        };
+
+        assert_eq!(run(&[]), "not_a\nend\n");
+        assert_eq!(run(&["A"]), "a\na_not_b\nend\n");
+        assert_eq!(run(&["A", "B"]), "a\na_b\nend\n");
+        assert_eq!(run(&["B"]), "not_a\nnot_a_b\nend\n");
+    }
+
+    #[test]
+    fn rejects_unbalanced_conditionals() {
+        let error = |source: &str| process(&[("main.wgsl", source)], "main.wgsl").unwrap_err();
+
+        let second_else = error("#ifdef A\n#else\n#else\n#endif");
+        assert_eq!(second_else.line, 3);
+        assert_eq!(second_else.message, "second #else for the same #ifdef");
+
+        // an #else of an enclosing block is still fine after a nested one
+        let files = [(
+            "main.wgsl",
+            "#ifdef A\n#ifdef B\n#else\n#endif\n#else\nx\n#endif",
+        )];
+        assert_eq!(process(&files, "main.wgsl").unwrap().source, "x\n");
+
+        assert_eq!(error("#else").message, "#else without #ifdef");
+        assert_eq!(error("#endif").message, "#endif without #ifdef");
+        assert_eq!(error("#ifdef A\nx").message, "unterminated #ifdef");
+    }
+
+    #[test]
+    fn source_map_points_at_original_lines() {
+        let files = [
+            (
+                "main.wgsl",
+                "// main\n#include \"common.wgsl\"\n#ifdef MISSING\nskipped\n#endif\nfn main() {}",
+            ),
+            ("common.wgsl", "\n#define ONE 1\nconst one = ONE;"),
+        ];
+        let shader = process(&files, "main.wgsl").unwrap();
+        assert_eq!(shader.source, "// main\n\nconst one = 1;\nfn main() {}\n");
+
+        let map = &shader.source_map;
+        assert_eq!(map.locate(1), Some(("main.wgsl", 1)));
+        assert_eq!(map.locate(2), Some(("common.wgsl", 1)));
+        assert_eq!(map.locate(3), Some(("common.wgsl", 3)));
+        assert_eq!(map.locate(4), Some(("main.wgsl", 6)));
+        assert_eq!(map.locate(0), None);
+        assert_eq!(map.locate(5), None);
+    }
+}
@@ -0,0 +1,600 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use naga::{
+    AddressSpace, ArraySize, Handle, Module, ScalarKind, ShaderStage, StorageAccess, Type,
+    TypeInner, VectorSize,
+};
+
+use super::shader::{validate_wgsl_with_info, ShaderError};
+
+/// Generates `#[repr(C)]` Pod structs with explicit padding for every struct reachable from a
+/// uniform or storage binding, plus `<NAME>_GROUP`/`<NAME>_BINDING` constants and a
+/// `<NAME>_LAYOUT_ENTRY` for each binding. The layout entry is visible to the stages whose entry
+/// points use the binding. Each struct comes with a compile-time check that its size matches
+/// naga's.
+///
+/// The output expects `sursface` and `bytemuck` with the `derive` feature in the including
+/// crate.
+pub fn generate_bindings(source: &str, label: &str) -> Result<String, ShaderError> {
+    let (module, info) = validate_wgsl_with_info(source, label)?;
+    let mut generator = Generator {
+        module: &module,
+        label,
+        emitted: HashSet::new(),
+        output: String::new(),
+    };
+
+    writeln!(
+        generator.output,
+        "// Generated by sursface from {}. Do not edit.",
+        label
+    )
+    .unwrap();
+
+    for (handle, global) in module.global_variables.iter() {
+        let (Some(name), Some(binding)) = (&global.name, &global.binding) else {
+            continue;
+        };
+        let buffer_type = match global.space {
+            AddressSpace::Uniform => "Uniform".to_owned(),
+            AddressSpace::Storage { access } => format!(
+                "Storage {{ read_only: {} }}",
+                !access.contains(StorageAccess::STORE)
+            ),
+            _ => continue,
+        };
+
+        let used: Vec<ShaderStage> = module
+            .entry_points
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !info.get_entry_point(*index)[handle].is_empty())
+            .map(|(_, entry_point)| entry_point.stage)
+            .collect();
+        let stages: Vec<_> = STAGES
+            .iter()
+            .filter(|(stage, _)| used.contains(stage))
+            .map(|(_, name)| format!("sursface::wgpu::ShaderStages::{}", name))
+            .collect();
+        let visibility = match stages.split_first() {
+            None => "sursface::wgpu::ShaderStages::NONE".to_owned(),
+            Some((first, rest)) => rest.iter().fold(first.clone(), |all, stage| {
+                format!("{}.union({})", all, stage)
+            }),
+        };
+
+        let constant = name.to_uppercase();
+        writeln!(generator.output).unwrap();
+        writeln!(
+            generator.output,
+            "pub const {}_GROUP: u32 = {};",
+            constant, binding.group
+        )
+        .unwrap();
+        writeln!(
+            generator.output,
+            "pub const {}_BINDING: u32 = {};",
+            constant, binding.binding
+        )
+        .unwrap();
+        writeln!(
+            generator.output,
+            "pub const {}_LAYOUT_ENTRY: sursface::wgpu::BindGroupLayoutEntry =
+    sursface::wgpu::BindGroupLayoutEntry {{
+        binding: {},
+        visibility: {},
+        ty: sursface::wgpu::BindingType::Buffer {{
+            ty: sursface::wgpu::BufferBindingType::{},
+            has_dynamic_offset: false,
+            min_binding_size: sursface::wgpu::BufferSize::new({}),
+        }},
+        count: None,
+    }};",
+            constant,
+            binding.binding,
+            visibility,
+            buffer_type,
+            generator.size_of(global.ty)
+        )
+        .unwrap();
+    }
+
+    for (_, global) in module.global_variables.iter() {
+        if matches!(
+            global.space,
+            AddressSpace::Uniform | AddressSpace::Storage { .. }
+        ) {
+            generator.emit_structs(global.ty)?;
+        }
+    }
+
+    Ok(generator.output)
+}
+
+/// Build script entry point: writes the bindings for `wgsl_path` to `out_path`.
+///
+/// ```ignore
+/// // build.rs
+/// let out_dir = std::env::var("OUT_DIR").unwrap();
+/// sursface::std::reflect::write_bindings(
+///     "src/assets/shader.wgsl",
+///     std::path::Path::new(&out_dir).join("bindings.rs"),
+/// )
+/// .unwrap();
+///
+/// // main.rs
+/// mod bindings {
+///     include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+/// }
+/// // create_uniforms(device, bindings::Uniforms { .. }, bindings::UNIFORMS_BINDING)
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_bindings(
+    wgsl_path: impl AsRef<std::path::Path>,
+    out_path: impl AsRef<std::path::Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let wgsl_path = wgsl_path.as_ref();
+    println!("cargo:rerun-if-changed={}", wgsl_path.display());
+
+    let source = std::fs::read_to_string(wgsl_path)?;
+    let bindings = generate_bindings(&source, &wgsl_path.display().to_string())?;
+    std::fs::write(out_path, bindings)?;
+
+    Ok(())
+}
+
+struct Generator<'a> {
+    module: &'a Module,
+    label: &'a str,
+    emitted: HashSet<Handle<Type>>,
+    output: String,
+}
+
+impl Generator<'_> {
+    fn error(&self, message: String) -> ShaderError {
+        ShaderError {
+            label: self.label.to_owned(),
+            diagnostic: format!("error: {}\n", message),
+            message,
+            location: None,
+        }
+    }
+
+    fn type_name(&self, ty: Handle<Type>) -> String {
+        let ty = &self.module.types[ty];
+        ty.name.clone().unwrap_or_else(|| format!("{:?}", ty.inner))
+    }
+
+    fn size_of(&self, ty: Handle<Type>) -> u32 {
+        self.module.types[ty].inner.size(self.module.to_ctx())
+    }
+
+    /// Emits every struct `ty` depends on before `ty` itself.
+    fn emit_structs(&mut self, ty: Handle<Type>) -> Result<(), ShaderError> {
+        match &self.module.types[ty].inner {
+            TypeInner::Array { base, .. } | TypeInner::BindingArray { base, .. } => {
+                self.emit_structs(*base)
+            }
+            TypeInner::Struct { members, span } => {
+                if !self.emitted.insert(ty) {
+                    return Ok(());
+                }
+                for member in members {
+                    self.emit_structs(member.ty)?;
+                }
+
+                let mut body = String::new();
+                let mut cursor = 0;
+                let mut pad_index = 0;
+
+                for (index, member) in members.iter().enumerate() {
+                    let name = member
+                        .name
+                        .clone()
+                        .unwrap_or_else(|| format!("field{}", index));
+
+                    if let TypeInner::Array {
+                        size: ArraySize::Dynamic,
+                        base,
+                        ..
+                    } = self.module.types[member.ty].inner
+                    {
+                        pad(&mut body, &mut pad_index, member.offset - cursor);
+                        writeln!(
+                            body,
+                            "    // `{}` is a runtime-sized array of `{}` starting at byte {}",
+                            name,
+                            self.rust_type(base)?,
+                            member.offset
+                        )
+                        .unwrap();
+                        cursor = member.offset;
+                        continue;
+                    }
+
+                    pad(&mut body, &mut pad_index, member.offset - cursor);
+                    writeln!(body, "    pub {}: {},", name, self.rust_type(member.ty)?).unwrap();
+                    cursor = member.offset + self.size_of(member.ty);
+                }
+
+                let end = match members.last() {
+                    Some(last) if is_runtime_array(&self.module.types[last.ty].inner) => cursor,
+                    _ => *span,
+                };
+                pad(&mut body, &mut pad_index, end - cursor);
+
+                writeln!(self.output).unwrap();
+                writeln!(self.output, "#[repr(C)]").unwrap();
+                writeln!(
+                    self.output,
+                    "#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]"
+                )
+                .unwrap();
+                writeln!(self.output, "#[allow(non_snake_case)]").unwrap();
+                writeln!(self.output, "pub struct {} {{", self.type_name(ty)).unwrap();
+                self.output.push_str(&body);
+                writeln!(self.output, "}}").unwrap();
+                writeln!(
+                    self.output,
+                    "const _: () = assert!(::core::mem::size_of::<{}>() == {});",
+                    self.type_name(ty),
+                    end
+                )
+                .unwrap();
+
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn rust_type(&self, ty: Handle<Type>) -> Result<String, ShaderError> {
+        let inner = &self.module.types[ty].inner;
+
+        Ok(match *inner {
+            TypeInner::Scalar(scalar) | TypeInner::Atomic(scalar) => self.scalar_type(scalar)?,
+            TypeInner::Vector { size, scalar } => {
+                format!("[{}; {}]", self.scalar_type(scalar)?, size as u8)
+            }
+            TypeInner::Matrix {
+                columns,
+                rows,
+                scalar,
+            } => {
+                // columns of a matNx3 are padded to 16 bytes, like a vec4
+                let rows = match rows {
+                    VectorSize::Tri => 4,
+                    rows => rows as u8,
+                };
+                format!(
+                    "[[{}; {}]; {}]",
+                    self.scalar_type(scalar)?,
+                    rows,
+                    columns as u8
+                )
+            }
+            TypeInner::Array {
+                base,
+                size: ArraySize::Constant(count),
+                stride,
+            } => {
+                let element = if stride == self.size_of(base) {
+                    self.rust_type(base)?
+                } else {
+                    match self.module.types[base].inner {
+                        TypeInner::Vector {
+                            size: VectorSize::Tri,
+                            scalar,
+                        } if stride == 4 * scalar.width as u32 => {
+                            format!("[{}; 4]", self.scalar_type(scalar)?)
+                        }
+                        _ => {
+                            return Err(self.error(format!(
+                                "array of `{}` with stride {} has no Pod representation",
+                                self.type_name(base),
+                                stride
+                            )))
+                        }
+                    }
+                };
+                format!("[{}; {}]", element, count)
+            }
+            TypeInner::Struct { .. } => self.type_name(ty),
+            _ => {
+                return Err(self.error(format!(
+                    "`{}` cannot be used in a host-shareable struct",
+                    self.type_name(ty)
+                )))
+            }
+        })
+    }
+
+    fn scalar_type(&self, scalar: naga::Scalar) -> Result<String, ShaderError> {
+        Ok(match (scalar.kind, scalar.width) {
+            (ScalarKind::Float, 4) => "f32",
+            (ScalarKind::Float, 2) => "u16",
+            (ScalarKind::Float, 8) => "f64",
+            (ScalarKind::Sint, 4) => "i32",
+            (ScalarKind::Uint, 4) => "u32",
+            (ScalarKind::Sint, 8) => "i64",
+            (ScalarKind::Uint, 8) => "u64",
+            (kind, width) => {
+                return Err(self.error(format!("unsupported scalar {:?} of width {}", kind, width)))
+            }
+        }
+        .to_owned())
+    }
+}
+
+/// Every stage with the name of its `wgpu::ShaderStages` flag, in bit order.
+const STAGES: [(ShaderStage, &str); 5] = [
+    (ShaderStage::Vertex, "VERTEX"),
+    (ShaderStage::Fragment, "FRAGMENT"),
+    (ShaderStage::Compute, "COMPUTE"),
+    (ShaderStage::Task, "TASK"),
+    (ShaderStage::Mesh, "MESH"),
+];
+
+fn is_runtime_array(inner: &TypeInner) -> bool {
+    matches!(
+        inner,
+        TypeInner::Array {
+            size: ArraySize::Dynamic,
+            ..
+        }
+    )
+}
+
+fn pad(body: &mut String, pad_index: &mut u32, bytes: u32) {
+    if bytes == 0 {
+        return;
+    }
+
+    if bytes.is_multiple_of(4) {
+        writeln!(body, "    pub _pad{}: [u32; {}],", pad_index, bytes / 4).unwrap();
+    } else {
+        writeln!(body, "    pub _pad{}: [u8; {}],", pad_index, bytes).unwrap();
+    }
+    *pad_index += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// The output for `fixtures/bindings.wgsl`, compiled to check it builds and matches naga.
+    #[allow(dead_code)]
+    mod generated {
+        include!("fixtures/bindings.rs");
+    }
+
+    /// Fields of every generated struct, in order, as name and Rust type.
+    fn parse_structs(output: &str) -> HashMap<String, Vec<(String, String)>> {
+        let mut structs = HashMap::new();
+        let mut current: Option<(String, Vec<_>)> = None;
+        for line in output.lines() {
+            if let Some(name) = line.strip_prefix("pub struct ") {
+                current = Some((name.trim_end_matches(" {").to_owned(), Vec::new()));
+            } else if line == "}" {
+                let (name, fields) = current.take().unwrap();
+                structs.insert(name, fields);
+            } else if let (Some((_, fields)), Some(field)) =
+                (&mut current, line.trim().strip_prefix("pub "))
+            {
+                let (name, ty) = field.trim_end_matches(',').split_once(": ").unwrap();
+                fields.push((name.to_owned(), ty.to_owned()));
+            }
+        }
+        structs
+    }
+
+    fn rust_size(ty: &str, structs: &HashMap<String, Vec<(String, String)>>) -> u32 {
+        if let Some(array) = ty.strip_prefix('[').and_then(|ty| ty.strip_suffix(']')) {
+            let (element, count) = array.rsplit_once("; ").unwrap();
+            return rust_size(element, structs) * count.parse::<u32>().unwrap();
+        }
+        match ty {
+            "u16" => 2,
+            "f32" | "i32" | "u32" => 4,
+            "f64" | "i64" | "u64" => 8,
+            name => structs[name]
+                .iter()
+                .map(|(_, ty)| rust_size(ty, structs))
+                .sum(),
+        }
+    }
+
+    /// Checks every generated struct against the offsets and sizes naga computed.
+    fn assert_matches_naga(source: &str) -> String {
+        let output = generate_bindings(source, "test.wgsl").unwrap();
+        let structs = parse_structs(&output);
+        let module = naga::front::wgsl::parse_str(source).unwrap();
+
+        for (_, ty) in module.types.iter() {
+            let (Some(name), TypeInner::Struct { members, span }) = (&ty.name, &ty.inner) else {
+                continue;
+            };
+            let fields = &structs[name];
+
+            let mut offsets = HashMap::new();
+            let mut offset = 0;
+            for (field, ty) in fields {
+                offsets.insert(field.as_str(), offset);
+                offset += rust_size(ty, &structs);
+            }
+
+            for member in members {
+                let member_name = member.name.as_deref().unwrap();
+                if is_runtime_array(&module.types[member.ty].inner) {
+                    assert_eq!(offset, member.offset, "{}.{}", name, member_name);
+                    continue;
+                }
+                assert_eq!(
+                    offsets[member_name], member.offset,
+                    "{}.{}",
+                    name, member_name
+                );
+            }
+            if !members
+                .last()
+                .is_some_and(|last| is_runtime_array(&module.types[last.ty].inner))
+            {
+                assert_eq!(offset, *span, "size of {}", name);
+                let check = format!("size_of::<{}>() == {});", name, span);
+                assert!(output.contains(&check), "no size check for {}", name);
+            }
+        }
+
+        output
+    }
+
+    #[test]
+    fn generated_bindings_compile() {
+        let source = include_str!("fixtures/bindings.wgsl");
+        let output = generate_bindings(source, "fixtures/bindings.wgsl").unwrap();
+        assert!(
+            output == include_str!("fixtures/bindings.rs"),
+            "fixtures/bindings.rs is out of date, regenerate it as:\n{}",
+            output
+        );
+
+        let module = naga::front::wgsl::parse_str(source).unwrap();
+        let naga_size = |name: &str| {
+            let (_, ty) = module
+                .types
+                .iter()
+                .find(|(_, ty)| ty.name.as_deref() == Some(name))
+                .unwrap();
+            ty.inner.size(module.to_ctx()) as usize
+        };
+        assert_eq!(size_of::<generated::Light>(), naga_size("Light"));
+        assert_eq!(size_of::<generated::Uniforms>(), naga_size("Uniforms"));
+        assert_eq!(size_of::<generated::Instance>(), naga_size("Instance"));
+        // Instances ends in a runtime array, which the struct stops short of.
+        assert_eq!(size_of::<generated::Instances>(), 16);
+
+        let buffer_size = |entry: wgpu::BindGroupLayoutEntry| match entry.ty {
+            wgpu::BindingType::Buffer {
+                min_binding_size, ..
+            } => min_binding_size.unwrap().get(),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            buffer_size(generated::UNIFORMS_LAYOUT_ENTRY),
+            size_of::<generated::Uniforms>() as u64
+        );
+        assert_eq!(generated::INSTANCES_GROUP, 1);
+        assert_eq!(
+            generated::INSTANCES_LAYOUT_ENTRY.visibility,
+            wgpu::ShaderStages::VERTEX
+        );
+    }
+
+    #[test]
+    fn pads_vec3_mat3_and_arrays_like_naga() {
+        let output = assert_matches_naga(
+            "struct Light {
+                direction: vec3<f32>,
+                color: vec3<f32>,
+            }
+            struct Uniforms {
+                a: vec3<f32>,
+                b: f32,
+                c: vec3<f32>,
+                normal: mat3x3<f32>,
+                points: array<vec3<f32>, 2>,
+                s: f32,
+                light: Light,
+                d: vec2<f32>,
+                m2: mat2x2<f32>,
+            }
+            @group(0) @binding(0) var<uniform> uniforms: Uniforms;",
+        );
+
+        let structs = parse_structs(&output);
+        let field = |name: &str| {
+            structs["Uniforms"]
+                .iter()
+                .find(|(field, _)| field == name)
+                .unwrap()
+                .1
+                .clone()
+        };
+        assert_eq!(field("normal"), "[[f32; 4]; 3]");
+        assert_eq!(field("points"), "[[f32; 4]; 2]");
+        assert_eq!(field("m2"), "[[f32; 2]; 2]");
+    }
+
+    #[test]
+    fn pads_storage_structs_like_naga() {
+        assert_matches_naga(
+            "struct Particle {
+                position: vec3<f32>,
+                mass: f32,
+                velocity: vec2<f32>,
+                weights: array<f32, 3>,
+            }
+            struct Particles {
+                count: u32,
+                origin: vec3<f32>,
+                items: array<Particle>,
+            }
+            @group(0) @binding(0) var<storage, read_write> particles: Particles;
+            @compute @workgroup_size(1)
+            fn main() {
+                particles.count = 0u;
+            }",
+        );
+    }
+
+    #[test]
+    fn layout_entries_follow_usage() {
+        let output = generate_bindings(
+            "struct Camera { view_proj: mat4x4<f32> }
+            struct Lights { count: u32, data: array<vec4<f32>> }
+            @group(0) @binding(0) var<uniform> camera: Camera;
+            @group(0) @binding(1) var<storage, read> lights: Lights;
+            @group(1) @binding(0) var<uniform> unused: vec4<f32>;
+
+            @vertex
+            fn vs_main() -> @builtin(position) vec4<f32> {
+                return camera.view_proj * vec4<f32>(0.0, 0.0, 0.0, 1.0);
+            }
+
+            @fragment
+            fn fs_main() -> @location(0) vec4<f32> {
+                return camera.view_proj[0] * f32(lights.count);
+            }",
+            "test.wgsl",
+        )
+        .unwrap();
+
+        let entry = |name: &str| {
+            let start = output
+                .find(&format!("pub const {}_LAYOUT_ENTRY", name))
+                .unwrap();
+            output[start..output[start..].find(";\n").unwrap() + start].to_owned()
+        };
+
+        let camera = entry("CAMERA");
+        assert!(camera.contains("binding: 0,"));
+        assert!(camera.contains(
+            "visibility: sursface::wgpu::ShaderStages::VERTEX.union(sursface::wgpu::ShaderStages::FRAGMENT),"
+        ));
+        assert!(camera.contains("BufferBindingType::Uniform,"));
+        assert!(camera.contains("BufferSize::new(64),"));
+
+        let lights = entry("LIGHTS");
+        assert!(lights.contains("visibility: sursface::wgpu::ShaderStages::FRAGMENT,"));
+        assert!(lights.contains("BufferBindingType::Storage { read_only: true },"));
+        // the fixed part plus one element
+        assert!(lights.contains("BufferSize::new(32),"));
+
+        let unused = entry("UNUSED");
+        assert!(unused.contains("visibility: sursface::wgpu::ShaderStages::NONE,"));
+        assert!(unused.contains("BufferSize::new(16),"));
+    }
+}
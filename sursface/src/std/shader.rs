@@ -0,0 +1,120 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use naga::valid::{Capabilities, ModuleInfo, ValidationFlags, Validator};
+use naga::SourceLocation;
+
+static VALIDATE_SHADERS: AtomicBool = AtomicBool::new(cfg!(debug_assertions));
+
+/// Enables or disables WGSL validation in `create_shader`. On by default in debug builds.
+pub fn set_shader_validation(enabled: bool) {
+    VALIDATE_SHADERS.store(enabled, Ordering::Relaxed);
+}
+
+pub fn shader_validation_enabled() -> bool {
+    VALIDATE_SHADERS.load(Ordering::Relaxed)
+}
+
+#[derive(Clone, Debug)]
+pub struct ShaderError {
+    pub label: String,
+    pub message: String,
+    /// 1-based line and column of the first span the error refers to.
+    pub location: Option<(u32, u32)>,
+    /// The error rendered against the source, with the offending spans annotated.
+    pub diagnostic: String,
+}
+
+impl ShaderError {
+    fn new(
+        label: &str,
+        message: String,
+        location: Option<SourceLocation>,
+        diagnostic: String,
+    ) -> Self {
+        Self {
+            label: label.to_owned(),
+            message,
+            location: location.map(|l| (l.line_number, l.line_position)),
+            diagnostic,
+        }
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.diagnostic)
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+/// Parses and validates WGSL on the CPU, without touching the GPU.
+///
+/// This is a syntax and type check only: every capability is allowed, so a shader that passes
+/// can still be rejected by a device that lacks a feature it uses, such as `f64` or push
+/// constants.
+pub fn validate_wgsl(source: &str, label: &str) -> Result<naga::Module, ShaderError> {
+    validate_wgsl_with_info(source, label).map(|(module, _)| module)
+}
+
+/// Like [`validate_wgsl`], also returning what the validator learned, such as which entry
+/// points use each global.
+pub(crate) fn validate_wgsl_with_info(
+    source: &str,
+    label: &str,
+) -> Result<(naga::Module, ModuleInfo), ShaderError> {
+    let module = naga::front::wgsl::parse_str(source).map_err(|e| {
+        ShaderError::new(
+            label,
+            e.message().to_owned(),
+            e.location(source),
+            e.emit_to_string_with_path(source, label),
+        )
+    })?;
+
+    let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|e| {
+            ShaderError::new(
+                label,
+                e.as_inner().to_string(),
+                e.location(source),
+                e.emit_to_string_with_path(source, label),
+            )
+        })?;
+
+    Ok((module, info))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_shader_passes() {
+        let source =
+            "@fragment\nfn fs_main() -> @location(0) vec4<f32> {\n    return vec4<f32>(1.0);\n}\n";
+        assert!(validate_wgsl(source, "valid.wgsl").is_ok());
+    }
+
+    #[test]
+    fn parse_error_names_label_and_line() {
+        let source = "fn main() {\n    let x = ;\n}\n";
+        let error = validate_wgsl(source, "broken.wgsl").unwrap_err();
+        assert_eq!(error.label, "broken.wgsl");
+        assert_eq!(error.location.map(|(line, _)| line), Some(2));
+        assert!(error.diagnostic.contains("broken.wgsl"));
+        assert!(error.diagnostic.contains("let x = ;"));
+    }
+
+    #[test]
+    fn validation_error_names_label_and_line() {
+        let source = "fn f() -> f32 {\n    return 1.0;\n}\nfn main() {\n    let x: i32 = f();\n}\n";
+        let error = validate_wgsl(source, "types.wgsl").unwrap_err();
+        assert_eq!(error.label, "types.wgsl");
+        assert_eq!(error.location.map(|(line, _)| line), Some(5));
+        assert!(error.diagnostic.contains("types.wgsl"));
+        assert!(error.diagnostic.contains("let x: i32 = f();"));
+    }
+}
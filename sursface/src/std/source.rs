@@ -0,0 +1,128 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use super::assets::AssetError;
+use super::preprocess::IncludeSource;
+
+/// Resolves asset paths to file contents, synchronously. The same loading code can run against
+/// files embedded in the binary, a directory on disk, or an in-memory map in tests.
+pub trait AssetSource: Send + Sync {
+    fn read(&self, path: &str) -> Result<Cow<'_, [u8]>, AssetError>;
+
+    fn read_to_string(&self, path: &str) -> Result<Cow<'_, str>, AssetError> {
+        match self.read(path)? {
+            Cow::Borrowed(bytes) => std::str::from_utf8(bytes)
+                .map(Cow::Borrowed)
+                .map_err(|e| AssetError::new(path, e)),
+            Cow::Owned(bytes) => String::from_utf8(bytes)
+                .map(Cow::Owned)
+                .map_err(|e| AssetError::new(path, e)),
+        }
+    }
+}
+
+/// Files compiled into the binary, usually generated by `sursface_build::embed_dir` in a build
+/// script:
+///
+/// ```ignore
+/// // build.rs
+/// sursface_build::embed_dir("assets", out_dir.join("assets.rs")).unwrap();
+///
+/// // main.rs
+/// static ASSETS: EmbeddedSource =
+///     EmbeddedSource::new(include!(concat!(env!("OUT_DIR"), "/assets.rs")));
+/// ```
+pub struct EmbeddedSource {
+    files: &'static [(&'static str, &'static [u8])],
+}
+
+impl EmbeddedSource {
+    pub const fn new(files: &'static [(&'static str, &'static [u8])]) -> Self {
+        Self { files }
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &'static str> {
+        self.files.iter().map(|(path, _)| *path)
+    }
+}
+
+impl AssetSource for EmbeddedSource {
+    fn read(&self, path: &str) -> Result<Cow<'_, [u8]>, AssetError> {
+        self.files
+            .iter()
+            .find(|(name, _)| *name == path)
+            .map(|(_, bytes)| Cow::Borrowed(*bytes))
+            .ok_or_else(|| AssetError::new(path, "not embedded"))
+    }
+}
+
+/// Files kept in memory, e.g. for tests.
+#[derive(Clone, Debug, Default)]
+pub struct MemorySource {
+    pub files: HashMap<String, Vec<u8>>,
+}
+
+impl MemorySource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, path: &str, bytes: impl Into<Vec<u8>>) -> Self {
+        self.insert(path, bytes);
+        self
+    }
+
+    pub fn insert(&mut self, path: &str, bytes: impl Into<Vec<u8>>) {
+        self.files.insert(path.to_owned(), bytes.into());
+    }
+}
+
+impl AssetSource for MemorySource {
+    fn read(&self, path: &str) -> Result<Cow<'_, [u8]>, AssetError> {
+        self.files
+            .get(path)
+            .map(|bytes| Cow::Borrowed(bytes.as_slice()))
+            .ok_or_else(|| AssetError::new(path, "not found"))
+    }
+}
+
+/// Files relative to a directory on disk.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct DirSource {
+    pub root: std::path::PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl DirSource {
+    pub fn new(root: impl Into<std::path::PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl AssetSource for DirSource {
+    fn read(&self, path: &str) -> Result<Cow<'_, [u8]>, AssetError> {
+        std::fs::read(self.root.join(path))
+            .map(Cow::Owned)
+            .map_err(|e| AssetError::new(path, e))
+    }
+}
+
+impl IncludeSource for EmbeddedSource {
+    fn read(&self, path: &str) -> Option<Cow<'_, str>> {
+        self.read_to_string(path).ok()
+    }
+}
+
+impl IncludeSource for MemorySource {
+    fn read(&self, path: &str) -> Option<Cow<'_, str>> {
+        self.read_to_string(path).ok()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl IncludeSource for DirSource {
+    fn read(&self, path: &str) -> Option<Cow<'_, str>> {
+        self.read_to_string(path).ok()
+    }
+}
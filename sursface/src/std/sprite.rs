@@ -0,0 +1,448 @@
+use std::ops::Range;
+
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, Color, CommandEncoder, Device, FilterMode, Queue,
+    RenderPass, RenderPipeline, Sampler, TextureView,
+};
+
+use crate::display::Display;
+
+use super::atlas::{AtlasRegion, TextureAtlas};
+use super::create_labeled_shader;
+use super::target::{load_target, RenderTarget, TargetFormat};
+
+const SHADER: &str = r#"
+struct Globals {
+    transform: mat4x4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> globals: Globals;
+@group(1) @binding(0) var t_sprite: texture_2d<f32>;
+@group(1) @binding(1) var s_sprite: sampler;
+
+struct Instance {
+    @location(0) position: vec2<f32>,
+    @location(1) size: vec2<f32>,
+    @location(2) origin: vec2<f32>,
+    @location(3) rotation: f32,
+    @location(4) uv_min: vec2<f32>,
+    @location(5) uv_max: vec2<f32>,
+    @location(6) tint: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) tint: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, sprite: Instance) -> VertexOutput {
+    // two triangles, corners in 0..1 with y down
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0), vec2<f32>(0.0, 1.0), vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0), vec2<f32>(1.0, 1.0), vec2<f32>(1.0, 0.0),
+    );
+    let corner = corners[index];
+
+    let local = (corner - sprite.origin) * sprite.size;
+    let c = cos(sprite.rotation);
+    let s = sin(sprite.rotation);
+    let rotated = vec2<f32>(local.x * c - local.y * s, local.x * s + local.y * c);
+
+    var output: VertexOutput;
+    output.position = globals.transform * vec4<f32>(sprite.position + rotated, 0.0, 1.0);
+    output.uv = mix(sprite.uv_min, sprite.uv_max, corner);
+    output.tint = sprite.tint;
+    return output;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_sprite, s_sprite, input.uv) * input.tint;
+}
+"#;
+
+/// Handle to a texture registered with [`SpriteBatch::add_texture`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TextureId(usize);
+
+#[derive(Clone, Copy, Debug)]
+pub struct Sprite {
+    pub texture: TextureId,
+    /// Where `origin` ends up, in pixels unless a custom transform is set.
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    pub scale: [f32; 2],
+    /// Clockwise, in radians, around `origin`.
+    pub rotation: f32,
+    /// Pivot for position and rotation, `(0, 0)` being the top left and `(1, 1)` the bottom right.
+    pub origin: [f32; 2],
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+    pub tint: Color,
+    /// Sprites with a higher depth are drawn on top.
+    pub depth: f32,
+}
+
+impl Sprite {
+    /// The whole texture stretched over `size`.
+    pub fn new(texture: TextureId, position: [f32; 2], size: [f32; 2]) -> Self {
+        Self {
+            texture,
+            position,
+            size,
+            scale: [1.0; 2],
+            rotation: 0.0,
+            origin: [0.0; 2],
+            uv_min: [0.0; 2],
+            uv_max: [1.0; 2],
+            tint: Color::WHITE,
+            depth: 0.0,
+        }
+    }
+
+    /// An atlas region at its pixel size.
+    pub fn from_region(texture: TextureId, region: &AtlasRegion, position: [f32; 2]) -> Self {
+        Self::new(
+            texture,
+            position,
+            [region.width as f32, region.height as f32],
+        )
+        .with_region(region)
+    }
+
+    pub fn with_region(mut self, region: &AtlasRegion) -> Self {
+        self.uv_min = region.uv_min;
+        self.uv_max = region.uv_max;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: [f32; 2]) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_origin(mut self, origin: [f32; 2]) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    pub fn with_tint(mut self, tint: Color) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    fn instance(&self) -> SpriteInstance {
+        SpriteInstance {
+            position: self.position,
+            size: [self.size[0] * self.scale[0], self.size[1] * self.scale[1]],
+            origin: self.origin,
+            rotation: self.rotation,
+            uv_min: self.uv_min,
+            uv_max: self.uv_max,
+            tint: [
+                self.tint.r as f32,
+                self.tint.g as f32,
+                self.tint.b as f32,
+                self.tint.a as f32,
+            ],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SpriteInstance {
+    position: [f32; 2],
+    size: [f32; 2],
+    origin: [f32; 2],
+    rotation: f32,
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    tint: [f32; 4],
+}
+
+/// Draws textured quads with one instance per sprite. Sprites are sorted by depth, then by
+/// texture, and every run of sprites sharing a texture is a single instanced draw call.
+///
+/// Works like [`super::draw2d::Draw2D`]: queue sprites with [`SpriteBatch::draw`], then
+/// [`SpriteBatch::prepare`] and [`SpriteBatch::render`], or [`SpriteBatch::flush`].
+pub struct SpriteBatch {
+    pipeline: RenderPipeline,
+    texture_layout: BindGroupLayout,
+    sampler: Sampler,
+    globals: Buffer,
+    globals_bind_group: BindGroup,
+    textures: Vec<BindGroup>,
+    instance_buffer: Buffer,
+    sprites: Vec<Sprite>,
+    /// Texture and instance range of each draw call uploaded by the last `prepare`.
+    batches: Vec<(TextureId, Range<u32>)>,
+    transform: Option<[[f32; 4]; 4]>,
+}
+
+impl SpriteBatch {
+    /// `filter` is used when sampling every texture, `Nearest` suiting pixel art.
+    pub fn new(device: &Device, target: &TargetFormat, filter: FilterMode) -> Self {
+        let shader = create_labeled_shader(device, SHADER, "Sprite Batch");
+
+        let globals = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sprite Globals"),
+            contents: bytemuck::cast_slice(&[pixel_transform(1, 1)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let globals_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Sprite Globals"),
+            entries: &[super::create_uniform_entry(0)],
+        });
+        let globals_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sprite Globals"),
+            layout: &globals_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: globals.as_entire_binding(),
+            }],
+        });
+
+        let texture_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Sprite Texture"),
+            entries: &[
+                RenderTarget::layout_entry(0),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: filter,
+            min_filter: filter,
+            ..Default::default()
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&globals_layout, &texture_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sprite Batch"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<SpriteInstance>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x2,
+                        1 => Float32x2,
+                        2 => Float32x2,
+                        3 => Float32,
+                        4 => Float32x2,
+                        5 => Float32x2,
+                        6 => Float32x4,
+                    ],
+                }],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: target.depth_format.map(|format| wgpu::DepthStencilState {
+                format,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: target.sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            texture_layout,
+            sampler,
+            globals,
+            globals_bind_group,
+            textures: Vec::new(),
+            instance_buffer: create_instance_buffer(device, 0),
+            sprites: Vec::new(),
+            batches: Vec::new(),
+            transform: None,
+        }
+    }
+
+    pub fn add_texture(&mut self, device: &Device, view: &TextureView) -> TextureId {
+        let bind_group = self.create_texture_bind_group(device, view);
+        self.textures.push(bind_group);
+
+        TextureId(self.textures.len() - 1)
+    }
+
+    /// Points `id` at `view`, e.g. after the texture behind it was recreated at a new size. The
+    /// previous bind group is dropped, so the old texture is freed once nothing else holds it.
+    pub fn replace_texture(&mut self, device: &Device, id: TextureId, view: &TextureView) {
+        self.textures[id.0] = self.create_texture_bind_group(device, view);
+    }
+
+    fn create_texture_bind_group(&self, device: &Device, view: &TextureView) -> BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sprite Texture"),
+            layout: &self.texture_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        })
+    }
+
+    pub fn add_atlas(&mut self, device: &Device, atlas: &TextureAtlas) -> TextureId {
+        self.add_texture(device, &atlas.view)
+    }
+
+    /// Replaces the default pixel-space projection, e.g. with a camera's view-projection
+    /// matrix. `None` goes back to pixels with `(0, 0)` at the top left.
+    pub fn set_transform(&mut self, transform: Option<[[f32; 4]; 4]>) {
+        self.transform = transform;
+    }
+
+    pub fn draw(&mut self, sprite: Sprite) {
+        self.sprites.push(sprite);
+    }
+
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.sprites.clear();
+    }
+
+    pub fn prepare(&mut self, device: &Device, queue: &Queue, width: u32, height: u32) {
+        let transform = self
+            .transform
+            .unwrap_or_else(|| pixel_transform(width, height));
+        queue.write_buffer(&self.globals, 0, bytemuck::cast_slice(&[transform]));
+
+        // stable, so sprites with equal keys keep the order they were drawn in
+        self.sprites
+            .sort_by(|a, b| a.depth.total_cmp(&b.depth).then(a.texture.cmp(&b.texture)));
+
+        let instances: Vec<SpriteInstance> = self.sprites.iter().map(Sprite::instance).collect();
+        let bytes: &[u8] = bytemuck::cast_slice(&instances);
+        if bytes.len() as u64 > self.instance_buffer.size() {
+            self.instance_buffer = create_instance_buffer(device, bytes.len());
+        }
+        queue.write_buffer(&self.instance_buffer, 0, bytes);
+
+        self.batches.clear();
+        for (index, sprite) in self.sprites.iter().enumerate() {
+            let index = index as u32;
+            match self.batches.last_mut() {
+                Some((texture, range)) if *texture == sprite.texture => range.end = index + 1,
+                _ => self.batches.push((sprite.texture, index..index + 1)),
+            }
+        }
+    }
+
+    pub fn render(&self, rpass: &mut RenderPass) {
+        if self.batches.is_empty() {
+            return;
+        }
+
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.globals_bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        for (texture, instances) in &self.batches {
+            rpass.set_bind_group(1, &self.textures[texture.0], &[]);
+            rpass.draw(0..6, instances.clone());
+        }
+    }
+
+    /// Draws the queued sprites over `target` in their own pass and clears the queue.
+    pub fn flush(
+        &mut self,
+        display: &Display,
+        encoder: &mut CommandEncoder,
+        target: &RenderTarget,
+    ) {
+        if !self.is_empty() {
+            self.prepare(&display.device, &display.queue, target.width, target.height);
+            let mut rpass = load_target(target, encoder);
+            self.render(&mut rpass);
+        }
+        self.clear();
+    }
+}
+
+/// Maps pixels, `(0, 0)` at the top left, to clip space.
+fn pixel_transform(width: u32, height: u32) -> [[f32; 4]; 4] {
+    let (width, height) = (width.max(1) as f32, height.max(1) as f32);
+
+    [
+        [2.0 / width, 0.0, 0.0, 0.0],
+        [0.0, -2.0 / height, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [-1.0, 1.0, 0.0, 1.0],
+    ]
+}
+
+fn create_instance_buffer(device: &Device, size: usize) -> Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Sprite Instances"),
+        size: (size.max(1024).next_power_of_two()) as u64,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shader_is_valid() {
+        crate::std::shader::validate_wgsl(SHADER, "Sprite Batch").unwrap();
+    }
+}
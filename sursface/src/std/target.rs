@@ -0,0 +1,293 @@
+use wgpu::{
+    BindGroupLayoutEntry, Color, CommandEncoder, Device, Face, FrontFace, PipelineLayout,
+    PolygonMode, PrimitiveState, PrimitiveTopology, RenderPass, RenderPipeline, ShaderModule,
+    SurfaceTexture, TextureFormat, TextureView, VertexBufferLayout,
+};
+
+use crate::display::Display;
+
+use super::create_render_texture;
+
+/// Everything a pipeline needs to know about the target it draws into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TargetFormat {
+    pub format: TextureFormat,
+    pub depth_format: Option<TextureFormat>,
+    pub sample_count: u32,
+}
+
+impl From<&Display<'_>> for TargetFormat {
+    fn from(display: &Display) -> Self {
+        Self {
+            format: display.config.format,
+            depth_format: None,
+            sample_count: 1,
+        }
+    }
+}
+
+/// A set of attachments that can be drawn into, whether it belongs to the swapchain or to an
+/// offscreen texture.
+pub struct RenderTarget {
+    pub color: TextureView,
+    /// Single-sampled view that `color` is resolved into when multisampling.
+    pub resolve: Option<TextureView>,
+    pub depth: Option<TextureView>,
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    pub depth_format: Option<TextureFormat>,
+    pub sample_count: u32,
+}
+
+impl RenderTarget {
+    pub fn from_view(view: TextureView, width: u32, height: u32, format: TextureFormat) -> Self {
+        Self {
+            color: view,
+            resolve: None,
+            depth: None,
+            width,
+            height,
+            format,
+            depth_format: None,
+            sample_count: 1,
+        }
+    }
+
+    /// An offscreen colour target whose final view can be bound as a texture.
+    pub fn offscreen(device: &Device, width: u32, height: u32, format: TextureFormat) -> Self {
+        let (_, view) = create_render_texture(device, width, height, format, 0);
+        Self::from_view(view, width, height, format)
+    }
+
+    /// Renders into a multisampled texture and resolves into the current colour view.
+    pub fn with_multisampling(mut self, device: &Device, sample_count: u32) -> Self {
+        if sample_count <= 1 {
+            return self;
+        }
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Multisampled Render Target"),
+            size: self.extent(),
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let msaa_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        self.resolve = Some(std::mem::replace(&mut self.color, msaa_view));
+        self.sample_count = sample_count;
+        self.depth = self
+            .depth_format
+            .map(|format| create_depth_view(device, self.width, self.height, format, sample_count));
+        self
+    }
+
+    pub fn with_depth(mut self, device: &Device, depth_format: TextureFormat) -> Self {
+        self.depth = Some(create_depth_view(
+            device,
+            self.width,
+            self.height,
+            depth_format,
+            self.sample_count,
+        ));
+        self.depth_format = Some(depth_format);
+        self
+    }
+
+    /// Uses an existing depth view, e.g. one kept alive across swapchain frames.
+    pub fn with_depth_view(mut self, view: TextureView, depth_format: TextureFormat) -> Self {
+        self.depth = Some(view);
+        self.depth_format = Some(depth_format);
+        self
+    }
+
+    /// The single-sampled view holding the final image, suitable for sampling.
+    pub fn view(&self) -> &TextureView {
+        self.resolve.as_ref().unwrap_or(&self.color)
+    }
+
+    pub fn layout_entry(binding_index: u32) -> BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding: binding_index,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        }
+    }
+
+    pub fn target_format(&self) -> TargetFormat {
+        TargetFormat {
+            format: self.format,
+            depth_format: self.depth_format,
+            sample_count: self.sample_count,
+        }
+    }
+
+    pub fn extent(&self) -> wgpu::Extent3d {
+        wgpu::Extent3d {
+            width: self.width,
+            height: self.height,
+            depth_or_array_layers: 1,
+        }
+    }
+}
+
+/// A swapchain image wrapped as a [`RenderTarget`]. Call [`Frame::present`] when done.
+pub struct Frame {
+    pub output: SurfaceTexture,
+    pub target: RenderTarget,
+}
+
+impl Frame {
+    pub fn present(self) {
+        self.output.present();
+    }
+}
+
+pub fn get_frame(display: &Display) -> Frame {
+    let (output, view) = super::get_framebuffer(display.surface());
+
+    Frame {
+        target: RenderTarget::from_view(
+            view,
+            display.config.width,
+            display.config.height,
+            display.config.format,
+        ),
+        output,
+    }
+}
+
+pub fn create_depth_view(
+    device: &Device,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+    sample_count: u32,
+) -> TextureView {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Depth Texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// Like [`super::clear`], but also clears the depth attachment and resolves multisampling.
+pub fn clear_target<'a>(
+    target: &'a RenderTarget,
+    encoder: &'a mut CommandEncoder,
+    color: Color,
+) -> RenderPass<'a> {
+    begin_pass(target, encoder, wgpu::LoadOp::Clear(color))
+}
+
+/// Starts a pass that draws over the existing contents of `target`.
+pub fn load_target<'a>(
+    target: &'a RenderTarget,
+    encoder: &'a mut CommandEncoder,
+) -> RenderPass<'a> {
+    begin_pass(target, encoder, wgpu::LoadOp::Load)
+}
+
+fn begin_pass<'a>(
+    target: &'a RenderTarget,
+    encoder: &'a mut CommandEncoder,
+    load: wgpu::LoadOp<Color>,
+) -> RenderPass<'a> {
+    let depth_load = match load {
+        wgpu::LoadOp::Clear(_) => wgpu::LoadOp::Clear(1.0),
+        wgpu::LoadOp::Load => wgpu::LoadOp::Load,
+    };
+
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: None,
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: &target.color,
+            resolve_target: target.resolve.as_ref(),
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: target.depth.as_ref().map(|view| {
+            wgpu::RenderPassDepthStencilAttachment {
+                view,
+                depth_ops: Some(wgpu::Operations {
+                    load: depth_load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }
+        }),
+        timestamp_writes: Default::default(),
+        occlusion_query_set: Default::default(),
+    })
+}
+
+/// Like [`super::create_render_pipeline`], but for any [`TargetFormat`]. Depth testing is
+/// enabled when the target has a depth format.
+pub fn create_target_pipeline(
+    device: &Device,
+    target: &TargetFormat,
+    pipeline_layout: PipelineLayout,
+    shader: ShaderModule,
+    buffers: &[VertexBufferLayout],
+) -> RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers,
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(target.format.into())],
+            compilation_options: Default::default(),
+        }),
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: Some(Face::Back),
+            polygon_mode: PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: target.depth_format.map(|format| wgpu::DepthStencilState {
+            format,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: target.sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+        cache: None,
+    })
+}
@@ -0,0 +1,867 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use image::RgbaImage;
+use wgpu::{Color, CommandEncoder, Device, FilterMode, Queue, RenderPass, Texture};
+
+use crate::display::Display;
+
+use super::sprite::{Sprite, SpriteBatch, TextureId};
+use super::target::{load_target, RenderTarget, TargetFormat};
+
+const INITIAL_ATLAS_SIZE: u32 = 512;
+/// Empty pixels kept between glyphs in the atlas so filtering doesn't bleed neighbours in.
+const PADDING: u32 = 1;
+
+#[derive(Clone, Debug)]
+pub struct FontError {
+    pub message: String,
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to load font: {}", self.message)
+    }
+}
+
+impl std::error::Error for FontError {}
+
+/// The glyphs queued since the last [`TextRenderer::clear`] don't fit in the largest atlas the
+/// device supports. Text queued before the glyph that didn't fit still renders.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AtlasFull;
+
+impl fmt::Display for AtlasFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "glyph atlas is full")
+    }
+}
+
+impl std::error::Error for AtlasFull {}
+
+enum FontSource {
+    Outline(fontdue::Font),
+    /// Coverage of a fixed grid of glyphs, laid out left to right, top to bottom.
+    Bitmap {
+        coverage: image::GrayImage,
+        cell_width: u32,
+        cell_height: u32,
+        columns: u32,
+        first: u32,
+        count: u32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct GlyphMetrics {
+    /// Offset of the bitmap's left edge from the pen position.
+    xmin: f32,
+    /// Offset of the bitmap's bottom edge above the baseline.
+    ymin: f32,
+    width: u32,
+    height: u32,
+    advance: f32,
+}
+
+/// A TTF/OTF font, rasterized on the CPU, or a bitmap font cut from a grid image. Both load from
+/// bytes, so they work the same on the web.
+pub struct Font {
+    source: FontSource,
+}
+
+impl Font {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FontError> {
+        let font = fontdue::Font::from_bytes(bytes, fontdue::FontSettings::default()).map_err(
+            |message| FontError {
+                message: message.to_owned(),
+            },
+        )?;
+
+        Ok(Self {
+            source: FontSource::Outline(font),
+        })
+    }
+
+    /// A monospaced font from an image of `cell_width` by `cell_height` glyphs, starting at
+    /// `first` (usually `' '`). Glyphs may be white on transparent or white on black. They are
+    /// scaled by whole multiples of the cell height.
+    pub fn from_bitmap(
+        bytes: &[u8],
+        cell_width: u32,
+        cell_height: u32,
+        first: char,
+    ) -> Result<Self, FontError> {
+        let image = image::load_from_memory(bytes)
+            .map_err(|error| FontError {
+                message: error.to_string(),
+            })?
+            .to_rgba8();
+
+        if cell_width == 0 || cell_height == 0 || image.width() < cell_width {
+            return Err(FontError {
+                message: format!(
+                    "{}x{} cells don't fit a {}x{} image",
+                    cell_width,
+                    cell_height,
+                    image.width(),
+                    image.height()
+                ),
+            });
+        }
+
+        let coverage = image::GrayImage::from_fn(image.width(), image.height(), |x, y| {
+            let [r, g, b, a] = image.get_pixel(x, y).0;
+            let luma = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000;
+            image::Luma([(luma as u8).min(a)])
+        });
+        let columns = image.width() / cell_width;
+        let count = columns * (image.height() / cell_height);
+
+        Ok(Self {
+            source: FontSource::Bitmap {
+                coverage,
+                cell_width,
+                cell_height,
+                columns,
+                first: first as u32,
+                count,
+            },
+        })
+    }
+
+    fn bitmap_scale(cell_height: u32, size: f32) -> u32 {
+        ((size / cell_height as f32).round() as u32).max(1)
+    }
+
+    /// Ascent, descent (negative) and line gap.
+    fn line_metrics(&self, size: f32) -> (f32, f32, f32) {
+        match &self.source {
+            FontSource::Outline(font) => font
+                .horizontal_line_metrics(size)
+                .map_or((size, 0.0, 0.0), |m| (m.ascent, m.descent, m.line_gap)),
+            FontSource::Bitmap { cell_height, .. } => {
+                let scale = Self::bitmap_scale(*cell_height, size);
+                ((cell_height * scale) as f32, 0.0, 0.0)
+            }
+        }
+    }
+
+    fn kern(&self, left: char, right: char, size: f32) -> f32 {
+        match &self.source {
+            FontSource::Outline(font) => font.horizontal_kern(left, right, size).unwrap_or(0.0),
+            FontSource::Bitmap { .. } => 0.0,
+        }
+    }
+
+    fn metrics(&self, ch: char, size: f32) -> GlyphMetrics {
+        match &self.source {
+            FontSource::Outline(font) => {
+                let metrics = font.metrics(ch, size);
+                GlyphMetrics {
+                    xmin: metrics.xmin as f32,
+                    ymin: metrics.ymin as f32,
+                    width: metrics.width as u32,
+                    height: metrics.height as u32,
+                    advance: metrics.advance_width,
+                }
+            }
+            FontSource::Bitmap {
+                cell_width,
+                cell_height,
+                ..
+            } => {
+                let scale = Self::bitmap_scale(*cell_height, size);
+                GlyphMetrics {
+                    xmin: 0.0,
+                    ymin: 0.0,
+                    width: cell_width * scale,
+                    height: cell_height * scale,
+                    advance: (cell_width * scale) as f32,
+                }
+            }
+        }
+    }
+
+    /// Coverage bitmap, one byte per pixel, row by row from the top.
+    fn rasterize(&self, ch: char, size: f32) -> (GlyphMetrics, Vec<u8>) {
+        match &self.source {
+            FontSource::Outline(font) => {
+                let (_, coverage) = font.rasterize(ch, size);
+                (self.metrics(ch, size), coverage)
+            }
+            FontSource::Bitmap {
+                coverage,
+                cell_width,
+                cell_height,
+                columns,
+                first,
+                count,
+            } => {
+                let metrics = self.metrics(ch, size);
+                let index = (ch as u32).wrapping_sub(*first);
+                if index >= *count {
+                    return (metrics, vec![0; (metrics.width * metrics.height) as usize]);
+                }
+
+                let (cell_x, cell_y) = (
+                    (index % columns) * cell_width,
+                    (index / columns) * cell_height,
+                );
+                let scale = metrics.width / cell_width;
+                let mut pixels = Vec::with_capacity((metrics.width * metrics.height) as usize);
+                for y in 0..metrics.height {
+                    for x in 0..metrics.width {
+                        pixels
+                            .push(coverage.get_pixel(cell_x + x / scale, cell_y + y / scale).0[0]);
+                    }
+                }
+
+                (metrics, pixels)
+            }
+        }
+    }
+}
+
+/// Handle to a font registered with [`TextRenderer::add_font`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FontId(usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TextStyle {
+    pub font: FontId,
+    /// Font size in pixels.
+    pub size: f32,
+    pub color: Color,
+    /// Lines wrap at word boundaries past this width.
+    pub max_width: Option<f32>,
+    /// Lines are aligned within `max_width`, or within the widest line without one.
+    pub align: Align,
+    /// Multiplier on the font's line height.
+    pub line_spacing: f32,
+    pub depth: f32,
+}
+
+impl TextStyle {
+    pub fn new(font: FontId, size: f32) -> Self {
+        Self {
+            font,
+            size,
+            color: Color::WHITE,
+            max_width: None,
+            align: Align::Left,
+            line_spacing: 1.0,
+            depth: 0.0,
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_max_width(mut self, max_width: f32) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    pub fn with_align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn with_line_spacing(mut self, line_spacing: f32) -> Self {
+        self.line_spacing = line_spacing;
+        self
+    }
+
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        self.depth = depth;
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    font: FontId,
+    ch: char,
+    size: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CachedGlyph {
+    metrics: GlyphMetrics,
+    /// Top left of the bitmap in the atlas, if it has any pixels.
+    position: Option<(u32, u32)>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct GlyphQuad {
+    atlas_position: (u32, u32),
+    position: [f32; 2],
+    size: [f32; 2],
+    color: Color,
+    depth: f32,
+}
+
+/// Rows of glyphs filled left to right; new glyphs go in the first row tall enough.
+struct Shelves {
+    width: u32,
+    height: u32,
+    /// Top, height and filled width of each row.
+    rows: Vec<(u32, u32, u32)>,
+}
+
+impl Shelves {
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let (width, height) = (width + PADDING, height + PADDING);
+
+        for (top, row_height, filled) in &mut self.rows {
+            if height <= *row_height && *filled + width <= self.width {
+                let x = *filled;
+                *filled += width;
+                return Some((x, *top));
+            }
+        }
+
+        let top = self.rows.last().map_or(0, |(top, h, _)| top + h);
+        if top + height > self.height || width > self.width {
+            return None;
+        }
+        self.rows.push((top, height, width));
+        Some((0, top))
+    }
+}
+
+/// Lays out and draws text through a [`SpriteBatch`], caching every glyph in an atlas that
+/// grows as needed, up to the device's largest texture. Once that is full, the cache is emptied
+/// at the start of the next frame. Usage mirrors the batch: queue text with
+/// [`TextRenderer::draw`], then [`TextRenderer::prepare`] and [`TextRenderer::render`], or
+/// [`TextRenderer::flush`].
+///
+/// ```ignore
+/// let mut text = TextRenderer::new(&display.device, &TargetFormat::from(&*display));
+/// let font = text.add_font(Font::from_bytes(include_bytes!("assets/font.ttf"))?);
+///
+/// text.draw("FPS: 60", [8.0, 8.0], &TextStyle::new(font, 16.0))?;
+/// text.flush(display, &mut encoder, &frame.target);
+/// ```
+pub struct TextRenderer {
+    batch: SpriteBatch,
+    fonts: Vec<Font>,
+    glyphs: HashMap<GlyphKey, CachedGlyph>,
+    pixels: RgbaImage,
+    shelves: Shelves,
+    max_atlas_size: u32,
+    texture: Option<(Texture, TextureId)>,
+    /// Part of `pixels` changed since the last upload, as left, top, right and bottom.
+    dirty: Option<[u32; 4]>,
+    quads: Vec<GlyphQuad>,
+}
+
+impl TextRenderer {
+    pub fn new(device: &Device, target: &TargetFormat) -> Self {
+        Self {
+            batch: SpriteBatch::new(device, target, FilterMode::Nearest),
+            fonts: Vec::new(),
+            glyphs: HashMap::new(),
+            pixels: RgbaImage::from_pixel(
+                INITIAL_ATLAS_SIZE,
+                INITIAL_ATLAS_SIZE,
+                image::Rgba([255, 255, 255, 0]),
+            ),
+            shelves: Shelves {
+                width: INITIAL_ATLAS_SIZE,
+                height: INITIAL_ATLAS_SIZE,
+                rows: Vec::new(),
+            },
+            max_atlas_size: device.limits().max_texture_dimension_2d,
+            texture: None,
+            dirty: None,
+            quads: Vec::new(),
+        }
+    }
+
+    pub fn add_font(&mut self, font: Font) -> FontId {
+        self.fonts.push(font);
+        FontId(self.fonts.len() - 1)
+    }
+
+    /// Width and height of `text` laid out with `style`.
+    pub fn measure(&self, text: &str, style: &TextStyle) -> (f32, f32) {
+        let font = &self.fonts[style.font.0];
+        let lines = self.wrap(text, style);
+        let line_height = line_height(font, style);
+
+        let width = lines
+            .iter()
+            .map(|line| line_width(font, line, style.size))
+            .fold(0.0, f32::max);
+        (width, line_height * lines.len() as f32)
+    }
+
+    /// Queues `text` with its top left corner at `position`, in pixels.
+    pub fn draw(
+        &mut self,
+        text: &str,
+        position: [f32; 2],
+        style: &TextStyle,
+    ) -> Result<(), AtlasFull> {
+        let lines = self.wrap(text, style);
+        let font = &self.fonts[style.font.0];
+        let (ascent, _, _) = font.line_metrics(style.size);
+        let line_height = line_height(font, style);
+
+        let widths: Vec<f32> = lines
+            .iter()
+            .map(|line| line_width(font, line, style.size))
+            .collect();
+        let container = style
+            .max_width
+            .unwrap_or_else(|| widths.iter().copied().fold(0.0, f32::max));
+
+        for (index, (line, width)) in lines.iter().zip(widths).enumerate() {
+            let offset = match style.align {
+                Align::Left => 0.0,
+                Align::Center => (container - width) / 2.0,
+                Align::Right => container - width,
+            };
+            let baseline = (position[1] + ascent + line_height * index as f32).round();
+            let mut pen = position[0] + offset;
+            let mut previous = None;
+
+            for ch in line.chars() {
+                let font = &self.fonts[style.font.0];
+                if let Some(previous) = previous {
+                    pen += font.kern(previous, ch, style.size);
+                }
+                previous = Some(ch);
+
+                let glyph = self.glyph(style.font, ch, style.size)?;
+                if let Some(atlas_position) = glyph.position {
+                    let metrics = glyph.metrics;
+                    self.quads.push(GlyphQuad {
+                        atlas_position,
+                        position: [
+                            (pen + metrics.xmin).round(),
+                            baseline - metrics.ymin - metrics.height as f32,
+                        ],
+                        size: [metrics.width as f32, metrics.height as f32],
+                        color: style.color,
+                        depth: style.depth,
+                    });
+                }
+                pen += glyph.metrics.advance;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn glyph(&mut self, font: FontId, ch: char, size: f32) -> Result<CachedGlyph, AtlasFull> {
+        let key = GlyphKey {
+            font,
+            ch,
+            size: size.to_bits(),
+        };
+        if let Some(glyph) = self.glyphs.get(&key) {
+            return Ok(*glyph);
+        }
+
+        let (metrics, coverage) = self.fonts[font.0].rasterize(ch, size);
+        let position = if metrics.width == 0 || metrics.height == 0 {
+            None
+        } else {
+            let position = loop {
+                if let Some(position) = self.shelves.allocate(metrics.width, metrics.height) {
+                    break position;
+                }
+                if self.shelves.width * 2 <= self.max_atlas_size {
+                    self.grow();
+                } else if self.quads.is_empty() && !self.shelves.rows.is_empty() {
+                    // nothing queued refers to the atlas yet, so it can start over
+                    self.evict();
+                } else {
+                    return Err(AtlasFull);
+                }
+            };
+
+            for y in 0..metrics.height {
+                for x in 0..metrics.width {
+                    let alpha = coverage[(y * metrics.width + x) as usize];
+                    self.pixels.put_pixel(
+                        position.0 + x,
+                        position.1 + y,
+                        image::Rgba([255, 255, 255, alpha]),
+                    );
+                }
+            }
+            self.mark_dirty(position, metrics.width, metrics.height);
+            Some(position)
+        };
+
+        let glyph = CachedGlyph { metrics, position };
+        self.glyphs.insert(key, glyph);
+        Ok(glyph)
+    }
+
+    /// Doubles the atlas, keeping existing glyphs where they are.
+    fn grow(&mut self) {
+        let size = self.shelves.width * 2;
+        let mut pixels = RgbaImage::from_pixel(size, size, image::Rgba([255, 255, 255, 0]));
+        image::imageops::replace(&mut pixels, &self.pixels, 0, 0);
+
+        self.pixels = pixels;
+        self.shelves.width = size;
+        self.shelves.height = size;
+    }
+
+    /// Forgets every cached glyph, keeping the atlas at its current size.
+    fn evict(&mut self) {
+        self.glyphs.clear();
+        self.shelves.rows.clear();
+        self.pixels = RgbaImage::from_pixel(
+            self.shelves.width,
+            self.shelves.height,
+            image::Rgba([255, 255, 255, 0]),
+        );
+        self.mark_dirty((0, 0), self.shelves.width, self.shelves.height);
+    }
+
+    fn mark_dirty(&mut self, (x, y): (u32, u32), width: u32, height: u32) {
+        let rect = [x, y, x + width, y + height];
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => [
+                dirty[0].min(rect[0]),
+                dirty[1].min(rect[1]),
+                dirty[2].max(rect[2]),
+                dirty[3].max(rect[3]),
+            ],
+            None => rect,
+        });
+    }
+
+    fn wrap<'a>(&self, text: &'a str, style: &TextStyle) -> Vec<&'a str> {
+        let font = &self.fonts[style.font.0];
+        let Some(max_width) = style.max_width else {
+            return text.split('\n').collect();
+        };
+
+        let width = |line: &str| line_width(font, line.trim_end(), style.size);
+
+        let mut lines = Vec::new();
+        for paragraph in text.split('\n') {
+            // `start..end` is the current line, up to the end of its last word
+            let (mut start, mut end) = (0, 0);
+            let mut index = 0;
+
+            for word in paragraph.split_inclusive(' ') {
+                let word_end = index + word.len();
+
+                if width(&paragraph[start..word_end]) > max_width {
+                    if end > start {
+                        lines.push(paragraph[start..end].trim_end());
+                        start = index;
+                    }
+
+                    // a single word wider than the line: break it between characters
+                    let word_start = start;
+                    for (offset, ch) in paragraph[word_start..word_end].char_indices() {
+                        let position = word_start + offset;
+                        let next = position + ch.len_utf8();
+                        if position > start && width(&paragraph[start..next]) > max_width {
+                            lines.push(&paragraph[start..position]);
+                            start = position;
+                        }
+                    }
+                }
+
+                end = word_end;
+                index = word_end;
+            }
+            lines.push(paragraph[start..end].trim_end());
+        }
+
+        lines
+    }
+
+    pub fn clear(&mut self) {
+        self.quads.clear();
+        self.batch.clear();
+    }
+
+    /// Uploads new glyphs and the queued text for a target of `width` by `height` pixels.
+    pub fn prepare(&mut self, device: &Device, queue: &Queue, width: u32, height: u32) {
+        let stale = self
+            .texture
+            .as_ref()
+            .is_none_or(|(texture, _)| texture.width() != self.pixels.width());
+        if stale {
+            // the atlas grew: upload it whole and point the batch at the new texture
+            let texture = super::atlas::upload_rgba(device, queue, &self.pixels);
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let id = match &self.texture {
+                Some((_, id)) => {
+                    self.batch.replace_texture(device, *id, &view);
+                    *id
+                }
+                None => self.batch.add_texture(device, &view),
+            };
+            self.texture = Some((texture, id));
+            self.dirty = None;
+        }
+        let (texture, id) = self.texture.as_ref().unwrap();
+
+        // only the glyphs added since the last upload
+        if let Some([left, top, right, bottom]) = self.dirty.take() {
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    origin: wgpu::Origin3d {
+                        x: left,
+                        y: top,
+                        z: 0,
+                    },
+                    ..texture.as_image_copy()
+                },
+                &self.pixels,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 4 * (top as u64 * self.pixels.width() as u64 + left as u64),
+                    bytes_per_row: Some(4 * self.pixels.width()),
+                    rows_per_image: None,
+                },
+                wgpu::Extent3d {
+                    width: right - left,
+                    height: bottom - top,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        let atlas_size = self.pixels.width() as f32;
+        self.batch.clear();
+        for quad in &self.quads {
+            let uv_min = [
+                quad.atlas_position.0 as f32 / atlas_size,
+                quad.atlas_position.1 as f32 / atlas_size,
+            ];
+            let uv_max = [
+                uv_min[0] + quad.size[0] / atlas_size,
+                uv_min[1] + quad.size[1] / atlas_size,
+            ];
+            self.batch.draw(Sprite {
+                uv_min,
+                uv_max,
+                ..Sprite::new(*id, quad.position, quad.size)
+                    .with_tint(quad.color)
+                    .with_depth(quad.depth)
+            });
+        }
+
+        self.batch.prepare(device, queue, width, height);
+    }
+
+    pub fn render(&self, rpass: &mut RenderPass) {
+        self.batch.render(rpass);
+    }
+
+    /// Draws the queued text over `target` in its own pass and clears the queue.
+    pub fn flush(
+        &mut self,
+        display: &Display,
+        encoder: &mut CommandEncoder,
+        target: &RenderTarget,
+    ) {
+        if !self.quads.is_empty() {
+            self.prepare(&display.device, &display.queue, target.width, target.height);
+            let mut rpass = load_target(target, encoder);
+            self.render(&mut rpass);
+        }
+        self.clear();
+    }
+}
+
+fn line_height(font: &Font, style: &TextStyle) -> f32 {
+    let (ascent, descent, line_gap) = font.line_metrics(style.size);
+    (ascent - descent + line_gap) * style.line_spacing
+}
+
+fn line_width(font: &Font, line: &str, size: f32) -> f32 {
+    let mut width = 0.0;
+    let mut previous = None;
+    for ch in line.chars() {
+        if let Some(previous) = previous {
+            width += font.kern(previous, ch, size);
+        }
+        width += font.metrics(ch, size).advance;
+        previous = Some(ch);
+    }
+    width
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::GpuContext;
+
+    /// Four solid 8x8 cells, `A` to `D`.
+    fn bitmap_font() -> Font {
+        let mut bytes = Vec::new();
+        RgbaImage::from_pixel(32, 8, image::Rgba([255, 255, 255, 255]))
+            .write_to(
+                &mut std::io::Cursor::new(&mut bytes),
+                image::ImageFormat::Png,
+            )
+            .unwrap();
+        Font::from_bitmap(&bytes, 8, 8, 'A').unwrap()
+    }
+
+    fn target() -> TargetFormat {
+        TargetFormat {
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            depth_format: None,
+            sample_count: 1,
+        }
+    }
+
+    #[test]
+    fn growing_replaces_the_texture_in_place() {
+        let gpu = GpuContext::noop();
+        let mut text = TextRenderer::new(&gpu.device, &target());
+        let font = text.add_font(bitmap_font());
+        // 256x256 glyphs, so a 512 atlas holds one
+        let style = TextStyle::new(font, 256.0);
+
+        text.draw("A", [0.0, 0.0], &style).unwrap();
+        text.prepare(&gpu.device, &gpu.queue, 64, 64);
+        let (_, id) = *text.texture.as_ref().unwrap();
+
+        text.draw("BC", [0.0, 0.0], &style).unwrap();
+        text.prepare(&gpu.device, &gpu.queue, 64, 64);
+        let (texture, grown_id) = text.texture.as_ref().unwrap();
+        assert_eq!(texture.width(), 1024);
+        assert_eq!(*grown_id, id);
+    }
+
+    #[test]
+    fn full_atlas_evicts_between_frames() {
+        let gpu = GpuContext::noop();
+        let mut text = TextRenderer::new(&gpu.device, &target());
+        text.max_atlas_size = INITIAL_ATLAS_SIZE;
+        let font = text.add_font(bitmap_font());
+        let style = TextStyle::new(font, 256.0);
+
+        text.draw("A", [0.0, 0.0], &style).unwrap();
+        assert_eq!(text.draw("B", [0.0, 0.0], &style), Err(AtlasFull));
+
+        text.clear();
+        text.draw("B", [0.0, 0.0], &style).unwrap();
+        assert_eq!(text.glyphs.len(), 1);
+        assert_eq!(text.shelves.width, INITIAL_ATLAS_SIZE);
+
+        // bigger than the largest atlas, even when empty
+        text.clear();
+        let huge = TextStyle::new(font, 1024.0);
+        assert_eq!(text.draw("C", [0.0, 0.0], &huge), Err(AtlasFull));
+    }
+
+    #[test]
+    fn only_new_glyphs_are_uploaded() {
+        let gpu = GpuContext::noop();
+        let mut text = TextRenderer::new(&gpu.device, &target());
+        let font = text.add_font(bitmap_font());
+        let style = TextStyle::new(font, 8.0);
+
+        text.draw("AB", [0.0, 0.0], &style).unwrap();
+        assert_eq!(text.dirty, Some([0, 0, 8 + PADDING + 8, 8]));
+        text.prepare(&gpu.device, &gpu.queue, 64, 64);
+        assert_eq!(text.dirty, None);
+
+        // cached glyphs don't touch the atlas
+        text.draw("BA", [0.0, 0.0], &style).unwrap();
+        assert_eq!(text.dirty, None);
+
+        text.draw("C", [0.0, 0.0], &style).unwrap();
+        let left = 2 * (8 + PADDING);
+        assert_eq!(text.dirty, Some([left, 0, left + 8, 8]));
+        text.prepare(&gpu.device, &gpu.queue, 64, 64);
+        assert_eq!(text.dirty, None);
+    }
+
+    #[test]
+    fn measure_counts_lines_and_scale() {
+        let gpu = GpuContext::noop();
+        let mut text = TextRenderer::new(&gpu.device, &target());
+        let font = text.add_font(bitmap_font());
+
+        assert_eq!(
+            text.measure("AB\nC", &TextStyle::new(font, 8.0)),
+            (16.0, 16.0)
+        );
+        // bitmap fonts scale by whole multiples of the cell
+        assert_eq!(
+            text.measure("AB\nC", &TextStyle::new(font, 17.0)),
+            (32.0, 32.0)
+        );
+        assert_eq!(
+            text.measure("A", &TextStyle::new(font, 8.0).with_line_spacing(1.5)),
+            (8.0, 12.0)
+        );
+        assert_eq!(text.measure("", &TextStyle::new(font, 8.0)), (0.0, 8.0));
+    }
+
+    #[test]
+    fn wrap_breaks_at_words_then_characters() {
+        let gpu = GpuContext::noop();
+        let mut text = TextRenderer::new(&gpu.device, &target());
+        let font = text.add_font(bitmap_font());
+        let style = TextStyle::new(font, 8.0);
+
+        assert_eq!(text.wrap("AB CD\n\nA", &style), ["AB CD", "", "A"]);
+
+        let narrow = style.with_max_width(24.0);
+        assert_eq!(text.wrap("AB CD", &narrow), ["AB", "CD"]);
+        assert_eq!(text.wrap("A B C", &narrow), ["A B", "C"]);
+        assert_eq!(text.wrap("ABCDABCD", &narrow), ["ABC", "DAB", "CD"]);
+        assert_eq!(text.wrap("A ABCD", &narrow), ["A", "ABC", "D"]);
+        assert_eq!(text.wrap("AB\nCD", &narrow), ["AB", "CD"]);
+        assert_eq!(text.measure("AB CD", &narrow), (16.0, 16.0));
+    }
+
+    #[test]
+    fn glyphs_follow_the_pen_and_alignment() {
+        let gpu = GpuContext::noop();
+        let mut text = TextRenderer::new(&gpu.device, &target());
+        let font = text.add_font(bitmap_font());
+        let style = TextStyle::new(font, 8.0);
+        let positions = |text: &mut TextRenderer| -> Vec<[f32; 2]> {
+            let positions = text.quads.iter().map(|quad| quad.position).collect();
+            text.clear();
+            positions
+        };
+
+        // bitmap fonts have no kerning, so glyphs sit one advance apart
+        text.draw("ABC", [4.0, 2.0], &style).unwrap();
+        assert_eq!(positions(&mut text), [[4.0, 2.0], [12.0, 2.0], [20.0, 2.0]]);
+
+        // without a max width, lines align within the widest one
+        text.draw("A\nABC", [0.0, 0.0], &style.with_align(Align::Right))
+            .unwrap();
+        assert_eq!(positions(&mut text)[0], [16.0, 0.0]);
+        text.draw("A\nABC", [0.0, 0.0], &style.with_align(Align::Center))
+            .unwrap();
+        assert_eq!(positions(&mut text)[0], [8.0, 0.0]);
+
+        let centered = style.with_max_width(40.0).with_align(Align::Center);
+        text.draw("ABC\nA", [0.0, 0.0], &centered).unwrap();
+        assert_eq!(
+            positions(&mut text),
+            [[8.0, 0.0], [16.0, 0.0], [24.0, 0.0], [16.0, 8.0]]
+        );
+    }
+}
@@ -13,3 +13,42 @@ pub fn now_secs_f64() -> f64 {
         .duration_since(*START_TIME)
         .as_secs_f64()
 }
+
+/// How the frames of a window are being drawn, updated by [`crate::app::App`] before each
+/// [`crate::app::AppState::draw`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameTiming {
+    /// Frames drawn so far, including the current one.
+    pub frame: u64,
+    /// Time since the previous frame started.
+    pub delta: web_time::Duration,
+    /// Frames per second, smoothed over the last few frames.
+    pub fps: f32,
+    /// The frame rate the app is limited to, if any.
+    pub target_fps: Option<f32>,
+    last_frame: Option<web_time::Instant>,
+}
+
+impl FrameTiming {
+    pub fn delta_secs(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    pub(crate) fn begin_frame(&mut self, now: web_time::Instant, target_fps: Option<f32>) {
+        self.frame += 1;
+        self.target_fps = target_fps;
+        self.delta = self
+            .last_frame
+            .map_or(web_time::Duration::ZERO, |last| now - last);
+        self.last_frame = Some(now);
+
+        if self.delta > web_time::Duration::ZERO {
+            let fps = 1.0 / self.delta_secs();
+            self.fps = if self.fps == 0.0 {
+                fps
+            } else {
+                self.fps + (fps - self.fps) * 0.1
+            };
+        }
+    }
+}
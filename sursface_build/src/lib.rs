@@ -0,0 +1,52 @@
+//! Build script helpers for sursface apps. Kept out of `sursface` itself so build scripts don't
+//! have to compile the renderer, whose cdylib output would collide with the regular build.
+
+/// For build scripts: writes the files under `dir` to `out_file` as the argument to
+/// `sursface::std::source::EmbeddedSource::new`, with paths relative to `dir` and `/` as
+/// separator.
+pub fn embed_dir(
+    dir: impl AsRef<std::path::Path>,
+    out_file: impl AsRef<std::path::Path>,
+) -> std::io::Result<()> {
+    use std::fmt::Write;
+
+    fn collect(dir: &std::path::Path, files: &mut Vec<std::path::PathBuf>) -> std::io::Result<()> {
+        println!("cargo:rerun-if-changed={}", dir.display());
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                collect(&path, files)?;
+            } else {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    let dir = std::fs::canonicalize(dir)?;
+    let mut files = Vec::new();
+    collect(&dir, &mut files)?;
+    files.sort();
+
+    let mut code = String::from("&[\n");
+    for file in &files {
+        println!("cargo:rerun-if-changed={}", file.display());
+        let relative = file
+            .strip_prefix(&dir)
+            .unwrap()
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        writeln!(
+            code,
+            "    ({:?}, include_bytes!({:?}) as &[u8]),",
+            relative,
+            file.to_string_lossy()
+        )
+        .unwrap();
+    }
+    code.push(']');
+
+    std::fs::write(out_file, code)
+}